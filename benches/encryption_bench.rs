@@ -38,9 +38,10 @@ pub fn criterion_data_encryption_decryption(c: &mut Criterion) {
     let config = config::Config::with_config_path(config::Environment::Dev, None);
     let state = rt.block_on(async { AppState::from_config(config).await });
     // create a DataKey in data_key_store
-    let identifier = Identifier::User(String::from("bench_user"));
+    let identifier = Identifier::new(String::from("User"), String::from("bench_user"));
     let key_create_req: CreateDataKeyRequest = CreateDataKeyRequest {
         identifier: identifier.clone(),
+        parent: None,
     };
     let tenant_state = state
         .tenant_states
@@ -70,7 +71,7 @@ pub fn criterion_data_encryption_decryption(c: &mut Criterion) {
                         black_box(rt.block_on(async {
                             bench_input
                                 .clone()
-                                .encrypt(&tenant_state, &identifier.clone())
+                                .encrypt(&tenant_state, &identifier.clone(), None, None)
                                 .await
                                 .expect("Failed while encrypting")
                         }))
@@ -90,7 +91,7 @@ pub fn criterion_data_encryption_decryption(c: &mut Criterion) {
             let bench_input = EncryptionType::Single(DecryptedData::from_data(value.into()));
             let encrypted_data = rt.block_on(async {
                 bench_input
-                    .encrypt(&tenant_state, &identifier)
+                    .encrypt(&tenant_state, &identifier, None, None)
                     .await
                     .expect("Failed while encrypting")
             });
@@ -145,7 +146,7 @@ pub fn criterion_batch_data_encryption_decryption(c: &mut Criterion) {
         .cloned()
         .expect("Invalid tenant");
 
-    let identifier = Identifier::User(String::from("bench_user"));
+    let identifier = Identifier::new(String::from("User"), String::from("bench_user"));
     {
         let mut group = c.benchmark_group("data-encryption-batch");
         (0..BATCH_BENCH_ITERATION).for_each(|po| {
@@ -160,7 +161,7 @@ pub fn criterion_batch_data_encryption_decryption(c: &mut Criterion) {
                         black_box(rt.block_on(async {
                             bench_input
                                 .clone()
-                                .encrypt(&tenant_state, &identifier.clone())
+                                .encrypt(&tenant_state, &identifier.clone(), None, None)
                                 .await
                                 .expect("Failed while encrypting")
                         }))
@@ -176,7 +177,7 @@ pub fn criterion_batch_data_encryption_decryption(c: &mut Criterion) {
             let decrypted_input = EncryptionType::Batch(generate_batch_data(input_size));
             let encrypted_bench_input = rt.block_on(async {
                 decrypted_input
-                    .encrypt(&tenant_state, &identifier)
+                    .encrypt(&tenant_state, &identifier, None, None)
                     .await
                     .expect("Failed while encrypting")
             });
@@ -234,7 +235,7 @@ pub fn criterion_multi_batch_data_encryption_decryption(c: &mut Criterion) {
         .cloned()
         .expect("Invalid tenant");
 
-    let identifier = Identifier::User(String::from("bench_user"));
+    let identifier = Identifier::new(String::from("User"), String::from("bench_user"));
     {
         let mut group = c.benchmark_group("data-encryption-multibatch");
         (0..BATCH_BENCH_ITERATION).for_each(|po| {
@@ -249,7 +250,7 @@ pub fn criterion_multi_batch_data_encryption_decryption(c: &mut Criterion) {
                         black_box(rt.block_on(async {
                             bench_input
                                 .clone()
-                                .encrypt(&tenant_state, &identifier.clone())
+                                .encrypt(&tenant_state, &identifier.clone(), None, None)
                                 .await
                                 .expect("Failed while encrypting")
                         }))
@@ -265,7 +266,7 @@ pub fn criterion_multi_batch_data_encryption_decryption(c: &mut Criterion) {
             let decrypted_input = EncryptionType::MultiBatch(generate_multi_batch_data(input_size));
             let encrypted_bench_input = rt.block_on(async {
                 decrypted_input
-                    .encrypt(&tenant_state, &identifier)
+                    .encrypt(&tenant_state, &identifier, None, None)
                     .await
                     .expect("Failed while encrypting")
             });
@@ -0,0 +1 @@
+ALTER TABLE data_key_store DROP COLUMN IF EXISTS key_metadata;
@@ -0,0 +1 @@
+ALTER TABLE data_key_store ADD COLUMN IF NOT EXISTS key_metadata TEXT;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS parent_index_data_key_store;
+
+ALTER TABLE data_key_store DROP COLUMN IF EXISTS parent_version;
+ALTER TABLE data_key_store DROP COLUMN IF EXISTS parent_data_identifier;
+ALTER TABLE data_key_store DROP COLUMN IF EXISTS parent_key_identifier;
@@ -0,0 +1,5 @@
+ALTER TABLE data_key_store ADD COLUMN IF NOT EXISTS parent_key_identifier VARCHAR(255);
+ALTER TABLE data_key_store ADD COLUMN IF NOT EXISTS parent_data_identifier VARCHAR(20);
+ALTER TABLE data_key_store ADD COLUMN IF NOT EXISTS parent_version INTEGER;
+
+CREATE INDEX IF NOT EXISTS parent_index_data_key_store ON data_key_store(parent_key_identifier,parent_data_identifier);
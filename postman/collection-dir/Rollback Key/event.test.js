@@ -0,0 +1,4 @@
+pm.test("Rollback Key - Status code is 200", function () {
+    pm.response.to.have.status(200);
+});
+
@@ -0,0 +1,11 @@
+-- The version column starts out as an integer, SQLite can not change the type of a column
+CREATE TABLE IF NOT EXISTS data_key_store (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key_identifier VARCHAR(255) NOT NULL,
+    data_identifier VARCHAR(20) NOT NULL,
+    encryption_key BLOB NOT NULL,
+    version INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX data_key_identifier_unique_index ON data_key_store(key_identifier,data_identifier,version);
@@ -0,0 +1 @@
+CREATE INDEX created_at_index_data_key_store ON data_key_store(created_at);
@@ -0,0 +1,2 @@
+-- The version column is created as an integer already
+SELECT 1;
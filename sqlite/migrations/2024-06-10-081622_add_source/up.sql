@@ -0,0 +1,2 @@
+ALTER TABLE data_key_store
+ADD source VARCHAR(30) NOT NULL DEFAULT 'AESLocal';
@@ -0,0 +1 @@
+ALTER TABLE data_key_store ADD COLUMN token VARCHAR(255);
@@ -0,0 +1 @@
+ALTER TABLE data_key_store ADD COLUMN key_metadata TEXT;
@@ -0,0 +1 @@
+ALTER TABLE data_key_store ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS data_key_rollbacks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key_identifier VARCHAR(255) NOT NULL,
+    data_identifier VARCHAR(20) NOT NULL,
+    version INTEGER NOT NULL,
+    rolled_back_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS rolled_back_at_index_data_key_rollbacks ON data_key_rollbacks(rolled_back_at);
@@ -3,24 +3,22 @@ pub mod tls;
 
 use std::sync::Arc;
 
-#[cfg(not(feature = "cassandra"))]
-use diesel_async::{AsyncPgConnection, pooled_connection::bb8::Pool};
 use rayon::{ThreadPool, ThreadPoolBuilder};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    config::{Config, TenantConfig},
-    crypto::KeyManagerClient,
+    config::{AutoProvisionConfig, Config, KeyBlockConfig, LeaseConfig, TenantConfig},
+    crypto::{KeyManagerClient, recipient::Recipients},
+    errors,
     multitenancy::{MultiTenant, TenantId, TenantState},
-    storage::{DbState, adapter},
+    storage::StorageState,
+    types::{Identifier, key::Version},
 };
 
-#[cfg(not(feature = "cassandra"))]
-pub(crate) type StorageState = DbState<Pool<AsyncPgConnection>, adapter::PostgreSQL>;
-
-#[cfg(feature = "cassandra")]
-pub(crate) type StorageState =
-    DbState<scylla::client::caching_session::CachingSession, adapter::Cassandra>;
+/// DER encoded leaf certificate the client authenticated its connection with, verified against
+/// the configured root CA. Only requests served over mTLS carry it.
+#[derive(Clone, Debug)]
+pub struct PeerCertificate(pub Vec<u8>);
 
 pub struct AppState {
     pub conf: Config,
@@ -43,12 +41,25 @@ impl AppState {
             tenant_states: tenants,
         }
     }
+
+    /// Spawns the polling of the rollbacks of every tenant, which drops the latest versions this
+    /// instance cached for keys rolled back on other instances
+    pub fn watch_rollbacks(&self) {
+        for tenant in self.tenant_states.values() {
+            tokio::task::spawn(Version::watch_rollbacks(tenant.clone()));
+        }
+    }
 }
 
 pub struct SessionState {
     pub cache_prefix: String,
     pub thread_pool: ThreadPool,
     pub keymanager_client: KeyManagerClient,
+    pub recipients: Recipients,
+    pub lease: LeaseConfig,
+    pub key_block: KeyBlockConfig,
+    pub data_identifiers: FxHashSet<String>,
+    pub auto_provision: AutoProvisionConfig,
     db_pool: StorageState,
 }
 
@@ -59,12 +70,22 @@ impl SessionState {
     #[allow(clippy::expect_used)]
     pub async fn from_config(config: &Config, tenant_config: &TenantConfig) -> Self {
         let secrets = config.secrets.clone();
-        let db_pool = StorageState::from_config(config, &tenant_config.schema).await;
+        let db_pool = StorageState::from_config(
+            config,
+            config.storage_backend(tenant_config),
+            &tenant_config.schema,
+        )
+        .await;
         let num_threads = config.pool_config.pool;
 
         Self {
             cache_prefix: tenant_config.cache_prefix.clone(),
             keymanager_client: secrets.create_keymanager_client().await,
+            recipients: config.recipients.clone(),
+            lease: config.lease,
+            key_block: config.key_block,
+            data_identifiers: FxHashSet::from_iter(tenant_config.data_identifiers.iter().cloned()),
+            auto_provision: tenant_config.auto_provision.clone(),
             db_pool,
             thread_pool: ThreadPoolBuilder::new()
                 .num_threads(num_threads)
@@ -73,7 +94,21 @@ impl SessionState {
         }
     }
 
-    pub fn db_pool(&self) -> &StorageState {
+    pub(crate) fn db_pool(&self) -> &StorageState {
         &self.db_pool
     }
+
+    /// Rejects identifiers with a `data_identifier` that is not configured for the tenant
+    pub(crate) fn validate_identifier(
+        &self,
+        identifier: &Identifier,
+    ) -> errors::CustomResult<(), errors::ApplicationErrorResponse> {
+        error_stack::ensure!(
+            self.data_identifiers.contains(identifier.data_identifier()),
+            errors::ApplicationErrorResponse::UnknownDataIdentifier(
+                identifier.data_identifier().to_string()
+            )
+        );
+        Ok(())
+    }
 }
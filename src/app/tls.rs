@@ -1,9 +1,60 @@
 use std::{io, sync::Arc};
 
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use futures::future::BoxFuture;
 use hyperswitch_masking::PeekInterface;
 use rustls::{ServerConfig, pki_types::CertificateDer, server::WebPkiClientVerifier};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower_http::add_extension::AddExtension;
 
-use crate::config::Config;
+use crate::{app::PeerCertificate, config::Config};
+
+/// Accepts TLS connections like [`RustlsAcceptor`] and adds the client certificate verified in the
+/// handshake to every request of the connection
+#[derive(Clone)]
+pub struct PeerCertificateAcceptor(RustlsAcceptor);
+
+impl PeerCertificateAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self(RustlsAcceptor::new(config))
+    }
+}
+
+impl<I, S> Accept<I, S> for PeerCertificateAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, PeerCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.0.clone();
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            // The client verifier rejects handshakes without a certificate chaining to the root CA
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certificates| certificates.first())
+                .map(|certificate| PeerCertificate(certificate.to_vec()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "No client certificate was verified",
+                    )
+                })?;
+
+            Ok((stream, AddExtension::new(service, certificate)))
+        })
+    }
+}
 
 pub async fn from_config(config: &Config) -> io::Result<ServerConfig> {
     let certs = config.certs.clone();
@@ -89,6 +89,8 @@ async fn main() {
     // Spawn metrics server without mtls in a seperate port
     tokio::task::spawn(spawn_metrics_server(state.clone()));
 
+    state.watch_rollbacks();
+
     #[cfg(feature = "mtls")]
     {
         use axum_server::tls_rustls::RustlsConfig;
@@ -98,7 +100,10 @@ async fn main() {
             .await
             .unwrap_or_else(|err| panic!("unable to read the certificates. got err:{err:?}"));
 
-        axum_server::bind_rustls(host, RustlsConfig::from_config(Arc::new(tls)))
+        axum_server::bind(host)
+            .acceptor(tls::PeerCertificateAcceptor::new(
+                RustlsConfig::from_config(Arc::new(tls)),
+            ))
             .serve(app.into_make_service())
             .await
             .expect("unable to start the server")
@@ -0,0 +1,52 @@
+#![allow(clippy::panic, clippy::expect_used)]
+
+use cripta::{
+    config,
+    env::{observability, observability as logger},
+    storage::copy::{Direction, copy_tenant},
+};
+
+/// Copies the data keys of tenants between Postgres and Cassandra.
+///
+/// Usage: `store-migrate <postgres-to-cassandra|cassandra-to-postgres> [tenant_id...]`, all
+/// tenants are copied when none are given.
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let direction = args
+        .next()
+        .expect("Missing direction, postgres-to-cassandra or cassandra-to-postgres")
+        .parse::<Direction>()
+        .unwrap_or_else(|err| panic!("{err:?}"));
+    let tenant_ids = args.collect::<Vec<_>>();
+
+    let config = config::Config::with_config_path(config::Environment::which(), None);
+    config.validate();
+
+    let _guard = observability::setup(
+        &config.log,
+        [env!("CARGO_BIN_NAME")],
+        env!("CARGO_BIN_NAME"),
+    )
+    .expect("Failed to initialize logging");
+
+    let mut consistent = true;
+    for (tenant_id, tenant) in &config.multitenancy.tenants.0 {
+        if !tenant_ids.is_empty() && !tenant_ids.contains(tenant_id) {
+            continue;
+        }
+
+        let report = copy_tenant(&config, tenant, direction)
+            .await
+            .unwrap_or_else(|err| panic!("Failed to copy the keys of {tenant_id}: {err:?}"));
+        logger::info!(tenant_id, ?report, consistent = report.is_consistent());
+        consistent &= report.is_consistent();
+    }
+
+    if !consistent {
+        logger::error!(
+            "Copied keys differ from the source, resolve the conflicts and run the copy again"
+        );
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,139 @@
+use base64::Engine;
+use error_stack::{IntoReport, ResultExt};
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+use serde::Deserialize;
+
+use crate::{
+    consts::base64::BASE64_ENGINE,
+    crypto::{Crypto, aes256::GcmAes256, hpke},
+    errors::{self, CustomResult},
+    types::{EncryptedData, key::Version},
+};
+
+/// Body of a `/key/lease` response.
+#[derive(Deserialize)]
+pub struct DataKeyLease {
+    pub key_version: Version,
+    pub key: StrongSecret<String>,
+    pub expires_at: i64,
+    pub max_uses: u64,
+}
+
+/// Data key leased from cripta for encrypting locally. Ciphertexts use the same `v{version}:`
+/// format as `/data/encrypt`, so they can be decrypted by the service. The lease limits are only
+/// enforced by this client.
+pub struct LeasedKey {
+    version: Version,
+    cipher: GcmAes256,
+    expires_at: i64,
+    remaining_uses: u64,
+}
+
+impl LeasedKey {
+    /// Lease delivered as a plaintext base64 key
+    pub fn new(lease: DataKeyLease) -> CustomResult<Self, errors::CryptoError> {
+        let key = BASE64_ENGINE
+            .decode(lease.key.peek())
+            .change_context(errors::CryptoError::InvalidKey)?;
+
+        Self::from_key(lease, key)
+    }
+
+    /// Lease sealed with HPKE to the client's X25519 public key
+    pub fn from_hpke(
+        lease: DataKeyLease,
+        private_key: &StrongSecret<Vec<u8>>,
+        info: &[u8],
+    ) -> CustomResult<Self, errors::CryptoError> {
+        let sealed = BASE64_ENGINE
+            .decode(lease.key.peek())
+            .change_context(errors::CryptoError::InvalidKey)?;
+        let key = hpke::open(private_key.peek(), info, &sealed)?;
+
+        Self::from_key(lease, key)
+    }
+
+    fn from_key(lease: DataKeyLease, key: Vec<u8>) -> CustomResult<Self, errors::CryptoError> {
+        let key =
+            <[u8; 32]>::try_from(key).map_err(|_| errors::CryptoError::InvalidKey.into_report())?;
+
+        Ok(Self {
+            version: lease.key_version,
+            cipher: GcmAes256::new(key.into())?,
+            expires_at: lease.expires_at,
+            remaining_uses: lease.max_uses,
+        })
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn remaining_uses(&self) -> u64 {
+        self.remaining_uses
+    }
+
+    pub fn is_expired(&self) -> bool {
+        time::OffsetDateTime::now_utc().unix_timestamp() >= self.expires_at
+    }
+
+    pub fn encrypt(
+        &mut self,
+        data: StrongSecret<Vec<u8>>,
+    ) -> CustomResult<EncryptedData, errors::CryptoError> {
+        error_stack::ensure!(
+            !self.is_expired() && self.remaining_uses > 0,
+            errors::CryptoError::LeaseExhausted
+        );
+        self.remaining_uses -= 1;
+
+        Ok(EncryptedData {
+            version: self.version,
+            data: self.cipher.encrypt(data)?,
+            wrapped_key: None,
+            purpose: None,
+            shared_keys: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(expires_at: i64, max_uses: u64) -> DataKeyLease {
+        DataKeyLease {
+            key_version: Version::from(3),
+            key: BASE64_ENGINE.encode([0x33_u8; 32]).into(),
+            expires_at,
+            max_uses,
+        }
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_leased_key_encrypts_in_service_format() {
+        let far_future = time::OffsetDateTime::now_utc().unix_timestamp() + 3600;
+        let mut key = LeasedKey::new(lease(far_future, 1)).unwrap();
+
+        let encrypted = key.encrypt(b"4111111111111111".to_vec().into()).unwrap();
+        let serialized = serde_json::to_value(&encrypted).unwrap();
+        assert!(serialized.as_str().unwrap().starts_with("v3:"));
+
+        let decrypted = GcmAes256::new([0x33_u8; 32].into())
+            .unwrap()
+            .decrypt(encrypted.inner())
+            .unwrap();
+        assert_eq!(decrypted.peek(), b"4111111111111111");
+
+        assert!(key.encrypt(b"exhausted".to_vec().into()).is_err());
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_expired_lease_is_rejected() {
+        let mut key = LeasedKey::new(lease(0, 10)).unwrap();
+
+        assert!(key.encrypt(b"expired".to_vec().into()).is_err());
+    }
+}
@@ -14,11 +14,16 @@ use crate::{
     crypto::{
         KeyManagerClient,
         aes256::GcmAes256,
+        recipient::Recipients,
         vault::{Vault, VaultSettings},
     },
     env::observability::LogConfig,
     errors::{self, CustomResult},
     services::aws::{AwsKmsClient, AwsKmsConfig},
+    types::{
+        Identifier,
+        identifier::{DEFAULT_DATA_IDENTIFIERS, MAX_DATA_IDENTIFIER_LEN},
+    },
 };
 
 pub mod vars {
@@ -134,10 +139,22 @@ pub struct Config {
     pub database: Database,
     pub secrets: Secrets,
     #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
     pub cassandra: Cassandra,
+    #[serde(default)]
+    pub sqlite: Sqlite,
+    #[serde(default)]
+    pub dynamodb: DynamoDb,
     pub log: LogConfig,
     pub multitenancy: MultiTenancy,
     pub pool_config: PoolConfig,
+    #[serde(default)]
+    pub recipients: Recipients,
+    #[serde(default)]
+    pub lease: LeaseConfig,
+    #[serde(default)]
+    pub key_block: KeyBlockConfig,
     #[cfg(feature = "mtls")]
     pub certs: Certs,
 }
@@ -154,6 +171,60 @@ pub struct TenantsConfig(pub FxHashMap<String, TenantConfig>);
 pub struct TenantConfig {
     pub schema: String,
     pub cache_prefix: String,
+    /// `data_identifier` types accepted for the tenant
+    #[serde(default = "default_data_identifiers")]
+    pub data_identifiers: Vec<String>,
+    #[serde(default)]
+    pub auto_provision: AutoProvisionConfig,
+    /// Overrides the backend of `[storage]` for the tenant
+    pub storage_backend: Option<StorageBackend>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Storage {
+    /// Postgres when not configured, deployments on Cassandra have to set it explicitly
+    #[serde(default)]
+    pub backend: StorageBackend,
+}
+
+/// Store of the data keys of a tenant
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    #[default]
+    Postgres,
+    Cassandra,
+    /// Kept in process memory and lost on restart, for tests and local development
+    Memory,
+    Sqlite,
+    #[serde(rename = "dynamodb")]
+    DynamoDb,
+}
+
+/// Creation of the first data key of an identifier on its first encryption
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AutoProvisionConfig {
+    /// Provisions missing keys on encryption, nothing is provisioned when disabled
+    pub enabled: bool,
+    /// Limits provisioning to these data identifiers, every data identifier of the tenant when
+    /// empty
+    pub data_identifiers: Vec<String>,
+}
+
+impl AutoProvisionConfig {
+    pub fn applies_to(&self, identifier: &Identifier) -> bool {
+        self.enabled
+            && (self.data_identifiers.is_empty()
+                || self
+                    .data_identifiers
+                    .iter()
+                    .any(|data_identifier| data_identifier == identifier.data_identifier()))
+    }
+}
+
+fn default_data_identifiers() -> Vec<String> {
+    DEFAULT_DATA_IDENTIFIERS.map(String::from).to_vec()
 }
 
 #[derive(Deserialize, Debug, Eq, PartialEq)]
@@ -162,6 +233,43 @@ pub struct Cassandra {
     pub timeout: u32,
     pub pool_size: NonZeroUsize,
     pub cache_size: usize,
+    #[serde(default)]
+    pub migrations: CassandraMigrations,
+}
+
+/// Creation of each tenant's keyspace and the CQL migrations applied to it at startup
+#[derive(Deserialize, Debug, Default, Eq, PartialEq)]
+#[serde(default)]
+pub struct CassandraMigrations {
+    pub run_on_startup: bool,
+    /// Replication factor per data center of created keyspaces. Keyspaces use `SimpleStrategy`
+    /// with a single replica when empty.
+    pub replication: FxHashMap<String, u32>,
+}
+
+/// Local SQLite databases for single node deployments, a database file per tenant schema
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Sqlite {
+    /// Directory holding the `{schema}.db` file of each tenant, created if missing
+    pub directory: PathBuf,
+    pub pool_size: Option<u32>,
+    /// Milliseconds a connection waits for another one to release the write lock
+    pub busy_timeout: u32,
+    pub migrations: DatabaseMigrations,
+}
+
+/// DynamoDB tables holding the keys, a table per tenant schema
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct DynamoDb {
+    pub region: String,
+    /// Endpoint of a DynamoDB Local instance, the regional AWS endpoint is used when unset
+    pub endpoint_url: Option<String>,
+    /// Prefix of the `{table_prefix}{schema}` table of each tenant
+    pub table_prefix: String,
+    /// Creates missing tables with on demand capacity at startup
+    pub create_tables: bool,
 }
 
 #[derive(Deserialize, Debug)]
@@ -175,6 +283,30 @@ pub struct Database {
     pub min_idle: Option<u32>,
     pub enable_ssl: Option<bool>,
     pub root_ca: Option<SecretContainer>,
+    #[serde(default)]
+    pub migrations: DatabaseMigrations,
+    /// Serves key lookups when set, with the credentials and database name of the primary
+    pub replica: Option<DatabaseReplica>,
+}
+
+/// Read replica of the database. Lookups fall back to the primary for keys the replica does not
+/// have, which covers keys created or rotated moments before.
+#[derive(Deserialize, Debug)]
+pub struct DatabaseReplica {
+    pub port: u16,
+    pub host: String,
+    pub pool_size: Option<u32>,
+    pub min_idle: Option<u32>,
+}
+
+/// Bootstrap of each tenant's schema with the embedded `migrations/` at startup. Migrations that
+/// drop data, like the token column drop, are left to the diesel CLI.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(default)]
+pub struct DatabaseMigrations {
+    pub run_on_startup: bool,
+    /// Only reports the pending migrations of each schema without applying them
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -194,6 +326,33 @@ pub struct Secrets {
     pub vault_config: VaultSettings,
 }
 
+/// Upper bounds for data keys leased to clients for local encryption
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct LeaseConfig {
+    /// Maximum lifetime of a lease in seconds
+    pub max_ttl: u64,
+    /// Maximum number of encryptions allowed with a leased key
+    pub max_uses: u64,
+}
+
+impl Default for LeaseConfig {
+    fn default() -> Self {
+        Self {
+            max_ttl: 300,
+            max_uses: 100_000,
+        }
+    }
+}
+
+/// Export of data keys as TR-31 key blocks
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(default)]
+pub struct KeyBlockConfig {
+    /// Allows exporting data keys which were not imported from a key block
+    pub export_generated_keys: bool,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Server {
     pub port: u16,
@@ -228,13 +387,36 @@ impl Default for Cassandra {
             timeout: 0,
             cache_size: 0,
             pool_size: NonZeroUsize::new(1).expect("The provided number is non zero"),
+            migrations: CassandraMigrations::default(),
+        }
+    }
+}
+
+impl Default for Sqlite {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("data"),
+            pool_size: None,
+            busy_timeout: 5000,
+            migrations: DatabaseMigrations::default(),
+        }
+    }
+}
+
+impl Default for DynamoDb {
+    fn default() -> Self {
+        Self {
+            region: String::from("us-east-1"),
+            endpoint_url: None,
+            table_prefix: String::from("data_key_store_"),
+            create_tables: false,
         }
     }
 }
 
 impl Cassandra {
-    fn validate(&self) -> CustomResult<(), errors::ParsingError> {
-        if cfg!(feature = "cassandra") {
+    fn validate(&self, required: bool) -> CustomResult<(), errors::ParsingError> {
+        if required {
             error_stack::ensure!(
                 !self.eq(&Self::default()),
                 errors::ParsingError::DecodingFailed(
@@ -255,7 +437,21 @@ impl MultiTenancy {
             errors::ParsingError::DecodingFailed("Failed to validate multitenancy configuration. You need to configure atleast one tenant".to_string()
          )
        );
-        Ok(())
+
+        self.tenants.0.iter().try_for_each(|(name, tenant)| {
+            tenant
+                .data_identifiers
+                .iter()
+                .try_for_each(|data_identifier| {
+                    error_stack::ensure!(
+                        Identifier::is_valid_data_identifier(data_identifier),
+                        errors::ParsingError::DecodingFailed(format!(
+                            "Invalid data identifier {data_identifier} configured for the tenant {name}, it must be 1 to {MAX_DATA_IDENTIFIER_LEN} ASCII alphanumeric characters"
+                        ))
+                    );
+                    Ok(())
+                })
+        })
     }
 }
 
@@ -294,6 +490,10 @@ impl Config {
         serde_path_to_error::deserialize(config)
             .expect("Unable to deserialize application configuration")
     }
+    pub fn storage_backend(&self, tenant: &TenantConfig) -> StorageBackend {
+        tenant.storage_backend.unwrap_or(self.storage.backend)
+    }
+
     /// # Panics
     ///
     /// Panics for a validation fail
@@ -304,12 +504,22 @@ impl Config {
             .expect("Failed to valdiate secrets some missing configuration found");
 
         self.cassandra
-            .validate()
+            .validate(
+                self.multitenancy
+                    .tenants
+                    .0
+                    .values()
+                    .any(|tenant| self.storage_backend(tenant) == StorageBackend::Cassandra),
+            )
             .expect("Failed to valdiate cassandra some missing configuration found");
 
         self.multitenancy
             .validate()
             .expect("Failed to validate multitenancy, some missing configuration found");
+
+        self.recipients
+            .validate()
+            .expect("Failed to validate recipients, invalid public key found");
     }
 }
 
@@ -3,6 +3,8 @@ pub(crate) const CONFIG_DIR: &str = "CONFIG_DIR";
 pub mod base64 {
     pub const BASE64_ENGINE: base64::engine::GeneralPurpose =
         base64::engine::general_purpose::STANDARD;
+    pub const BASE64_URL_SAFE_ENGINE: base64::engine::GeneralPurpose =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD;
 }
 
 pub const X_REQUEST_ID: &str = "x-request-id";
@@ -1,6 +1,7 @@
 mod crux;
 mod decryption;
 mod encryption;
+mod reencryption;
 
 use axum::extract::Json;
 pub use crux::*;
@@ -10,8 +11,8 @@ use crate::{
     errors, metrics,
     multitenancy::TenantState,
     types::{
-        requests::{DecryptionRequest, EncryptDataRequest},
-        response::{DecryptionResponse, EncryptionResponse},
+        requests::{DecryptionRequest, EncryptDataRequest, ReEncryptDataRequest},
+        response::{DecryptionResponse, EncryptionResponse, ReEncryptionResponse},
     },
     utils,
 };
@@ -49,3 +50,20 @@ pub async fn decrypt_data(
     )
     .await
 }
+
+pub async fn reencrypt_data(
+    state: TenantState,
+    Json(req): Json<ReEncryptDataRequest>,
+) -> errors::ApiResponseResult<Json<ReEncryptionResponse>> {
+    let (data_identifier, key_identifier) = req.identifier.get_identifier();
+
+    utils::record_api_operation(
+        reencryption::reencryption(state, req),
+        &metrics::REENCRYPTION_API_LATENCY,
+        &[
+            KeyValue::new("data_identifier", data_identifier),
+            KeyValue::new("key_identifier", key_identifier),
+        ],
+    )
+    .await
+}
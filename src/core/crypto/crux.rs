@@ -1,18 +1,19 @@
 use std::str::FromStr;
 
 use error_stack::IntoReport;
-use hyperswitch_masking::PeekInterface;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
 use rayon::prelude::*;
 use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    crypto::{Crypto, Source, aes256::GcmAes256},
+    crypto::{Crypto, Source, aes256::GcmAes256, kcv, purpose},
     errors::{self, SwitchError},
     multitenancy::TenantState,
     storage::types::{DataKey, DataKeyNew},
     types::{
         DecryptedData, DecryptedDataGroup, EncryptedData, EncryptedDataGroup, Identifier, Key,
-        MultipleDecryptionDataGroup, MultipleEncryptionDataGroup, key::Version,
+        MultipleDecryptionDataGroup, MultipleEncryptionDataGroup, SharedKey,
+        key::{ParentKey, Version},
     },
 };
 
@@ -38,18 +39,39 @@ impl KeyEncrypter<DataKeyNew> for Key {
         self,
         state: &TenantState,
     ) -> errors::CustomResult<DataKeyNew, errors::CryptoError> {
-        let encryption_key = state
-            .keymanager_client
-            .encrypt_key(self.key.peek().to_vec().into())
-            .await?;
+        let encryption_key = match &self.parent {
+            Some(parent) => {
+                let parent_key = Key::get_key(state, &parent.identifier, parent.version)
+                    .await
+                    .switch()?;
+                GcmAes256::new(parent_key.key)?.encrypt(self.key.peek().to_vec().into())?
+            }
+            None => {
+                state
+                    .keymanager_client
+                    .encrypt_key(self.key.peek().to_vec().into())
+                    .await?
+            }
+        };
 
         let (data_identifier, key_identifier) = self.identifier.get_identifier();
+        let (parent_data_identifier, parent_key_identifier) = self
+            .parent
+            .as_ref()
+            .map(|parent| parent.identifier.get_identifier())
+            .unzip();
         Ok(DataKeyNew {
             data_identifier,
             key_identifier,
             encryption_key,
             version: self.version,
             source: self.source.to_string(),
+            key_metadata: None,
+            kcv: Some(kcv::compute(&self.key)?),
+            parent_key_identifier,
+            parent_data_identifier,
+            parent_version: self.parent.as_ref().map(|parent| parent.version),
+            is_active: true,
             created_at: time::PrimitiveDateTime::new(
                 time::OffsetDateTime::now_utc().date(),
                 time::OffsetDateTime::now_utc().time(),
@@ -61,11 +83,78 @@ impl KeyEncrypter<DataKeyNew> for Key {
 #[async_trait::async_trait]
 impl KeyDecrypter<Key> for DataKey {
     async fn decrypt(self, state: &TenantState) -> errors::CustomResult<Key, errors::CryptoError> {
-        let decrypted_key = state
+        let parent = self.parent().switch()?;
+        let decrypted_key = match &parent {
+            Some(parent) => {
+                let parent_key = Key::get_key(state, &parent.identifier, parent.version)
+                    .await
+                    .switch()?;
+                GcmAes256::new(parent_key.key)?.decrypt(self.encryption_key)?
+            }
+            None => {
+                state
+                    .keymanager_client
+                    .decrypt_key(self.encryption_key)
+                    .await?
+            }
+        };
+
+        self.into_key(decrypted_key, parent)
+    }
+}
+
+#[async_trait::async_trait]
+impl KeyDecrypter<Vec<Key>> for Vec<DataKey> {
+    /// Keys wrapped by the KEK are unwrapped together by the key manager, with a single request
+    /// where the backend supports batches and concurrently otherwise
+    async fn decrypt(
+        self,
+        state: &TenantState,
+    ) -> errors::CustomResult<Vec<Key>, errors::CryptoError> {
+        let mut wrapped_by_kek = Vec::new();
+        let mut wrapped_by_parent = Vec::new();
+        for data_key in self {
+            if data_key.parent().switch()?.is_some() {
+                wrapped_by_parent.push(data_key);
+            } else {
+                wrapped_by_kek.push(data_key);
+            }
+        }
+
+        let decrypted_keys = state
             .keymanager_client
-            .decrypt_key(self.encryption_key)
+            .decrypt_keys(
+                wrapped_by_kek
+                    .iter()
+                    .map(|data_key| data_key.encryption_key.clone())
+                    .collect(),
+            )
             .await?;
+        let mut keys = wrapped_by_kek
+            .into_iter()
+            .zip(decrypted_keys)
+            .map(|(data_key, decrypted_key)| data_key.into_key(decrypted_key, None))
+            .collect::<errors::CustomResult<Vec<_>, _>>()?;
+
+        keys.extend(
+            futures::future::try_join_all(
+                wrapped_by_parent
+                    .into_iter()
+                    .map(|data_key| data_key.decrypt(state)),
+            )
+            .await?,
+        );
+
+        Ok(keys)
+    }
+}
 
+impl DataKey {
+    fn into_key(
+        self,
+        decrypted_key: StrongSecret<Vec<u8>>,
+        parent: Option<ParentKey>,
+    ) -> errors::CustomResult<Key, errors::CryptoError> {
         let decrypted_key = <[u8; 32]>::try_from(decrypted_key.peek().to_vec())
             .map_err(|_| errors::CryptoError::DecryptionFailed("KMS").into_report())?;
 
@@ -78,16 +167,32 @@ impl KeyDecrypter<Key> for DataKey {
             version: self.version,
             key: decrypted_key.into(),
             source,
+            parent,
         })
     }
 }
 
+/// Data key to encrypt with, which is the latest version of the identifier unless one is pinned
+async fn encryption_key(
+    state: &TenantState,
+    identifier: &Identifier,
+    version: Option<Version>,
+) -> errors::CustomResult<Key, errors::CryptoError> {
+    let version = match version {
+        Some(version) => version,
+        None => Version::get_latest(identifier, state).await,
+    };
+    Key::get_key(state, identifier, version).await.switch()
+}
+
 #[async_trait::async_trait]
 pub trait DataEncrypter<ToType> {
     async fn encrypt(
         self,
         state: &TenantState,
         identifier: &Identifier,
+        purpose: Option<&str>,
+        version: Option<Version>,
     ) -> errors::CustomResult<ToType, errors::CryptoError>;
 }
 
@@ -106,11 +211,12 @@ impl DataEncrypter<MultipleEncryptionDataGroup> for MultipleDecryptionDataGroup
         self,
         state: &TenantState,
         identifier: &Identifier,
+        purpose: Option<&str>,
+        version: Option<Version>,
     ) -> errors::CustomResult<MultipleEncryptionDataGroup, errors::CryptoError> {
-        let version = Version::get_latest(identifier, state).await;
-        let decrypted_key = Key::get_key(state, identifier, version).await.switch()?;
+        let decrypted_key = encryption_key(state, identifier, version).await?;
 
-        let key = GcmAes256::new(decrypted_key.key)?;
+        let key = data_cipher(&decrypted_key, purpose)?;
         let chunk_size = std::cmp::max(self.0.len() / state.thread_pool.current_num_threads(), 1);
 
         // Helper closure to encrypt a single DecryptedDataGroup into an EncryptedDataGroup.
@@ -128,6 +234,9 @@ impl DataEncrypter<MultipleEncryptionDataGroup> for MultipleDecryptionDataGroup
                         EncryptedData {
                             version: decrypted_key.version,
                             data: encrypted_data,
+                            wrapped_key: None,
+                            purpose: purpose.map(str::to_string),
+                            shared_keys: Vec::new(),
                         },
                     ))
                 })
@@ -170,23 +279,27 @@ impl DataDecrypter<MultipleDecryptionDataGroup> for MultipleEncryptionDataGroup
         let versions = self
             .0
             .iter()
-            .flat_map(|group| group.0.values().map(|data| data.version))
+            .flat_map(|group| group.0.values())
+            .filter(|data| !data.has_embedded_key())
+            .map(|data| data.version)
             .collect::<FxHashSet<_>>();
 
         let decrypted_keys = Key::get_multiple_keys(state, identifier, versions)
             .await
             .switch()?;
+        let embedded_keys = unwrap_embedded_keys(
+            state,
+            identifier,
+            self.0.iter().flat_map(|group| group.0.values()),
+        )
+        .await?;
 
         let chunk_size = std::cmp::max(self.0.len() / state.thread_pool.current_num_threads(), 1);
 
         // Helper closure to decrypt a single entity from an encrypted group.
         let decrypt_entity = |(hash_key, data): (String, EncryptedData)| -> errors::CustomResult<(String, DecryptedData), _> {
-            let version = data.version;
-            let decrypted_key = decrypted_keys.get(&version)
-            .ok_or_else(|| errors::CryptoError::DecryptionFailed("AES").into_report())?;
-            let key = GcmAes256::new(decrypted_key.key.clone())?;
-            let decrypted_data = key.decrypt(data.inner())?;
-            Ok((hash_key, DecryptedData::from_data(decrypted_data)))
+            let decrypted_data = decrypt_with(data, identifier, &decrypted_keys, &embedded_keys)?;
+            Ok((hash_key, decrypted_data))
         };
 
         // Helper closure to decrypt an entire group.
@@ -231,10 +344,11 @@ impl DataEncrypter<EncryptedDataGroup> for DecryptedDataGroup {
         self,
         state: &TenantState,
         identifier: &Identifier,
+        purpose: Option<&str>,
+        version: Option<Version>,
     ) -> errors::CustomResult<EncryptedDataGroup, errors::CryptoError> {
-        let version = Version::get_latest(identifier, state).await;
-        let decrypted_key = Key::get_key(state, identifier, version).await.switch()?;
-        let key = GcmAes256::new(decrypted_key.key)?;
+        let decrypted_key = encryption_key(state, identifier, version).await?;
+        let key = data_cipher(&decrypted_key, purpose)?;
 
         state.thread_pool.install(|| {
             self.0
@@ -244,6 +358,9 @@ impl DataEncrypter<EncryptedDataGroup> for DecryptedDataGroup {
                     Ok::<_, error_stack::Report<errors::CryptoError>>((hash_key,EncryptedData {
                         version: decrypted_key.version,
                         data: encrypted_data,
+                        wrapped_key: None,
+                        purpose: purpose.map(str::to_string),
+                        shared_keys: Vec::new(),
                     }))
                 })
                 .collect::<errors::CustomResult<FxHashMap<String, EncryptedData>,errors::CryptoError>>()
@@ -258,10 +375,16 @@ impl DataDecrypter<DecryptedDataGroup> for EncryptedDataGroup {
         state: &TenantState,
         identifier: &Identifier,
     ) -> errors::CustomResult<DecryptedDataGroup, errors::CryptoError> {
-        let version = FxHashSet::from_iter(self.0.values().map(|d| d.version));
+        let version = FxHashSet::from_iter(
+            self.0
+                .values()
+                .filter(|d| !d.has_embedded_key())
+                .map(|d| d.version),
+        );
         let decrypted_keys = Key::get_multiple_keys(state, identifier, version)
             .await
             .switch()?;
+        let embedded_keys = unwrap_embedded_keys(state, identifier, self.0.values()).await?;
 
         state
             .thread_pool
@@ -270,17 +393,8 @@ impl DataDecrypter<DecryptedDataGroup> for EncryptedDataGroup {
             .0
             .into_par_iter()
             .map(|(hash_key, data)| {
-                let version = data.version;
-                let decrypted_key = decrypted_keys
-                    .get(&version)
-                    .ok_or(errors::CryptoError::DecryptionFailed("AES").into_report())?.clone();
-
-                let key = GcmAes256::new(decrypted_key.key)?;
-                let decrypted_data = key.decrypt(data.inner())?;
-                Ok::<_, error_stack::Report<errors::CryptoError>>((
-                    hash_key,
-                    DecryptedData::from_data(decrypted_data),
-                ))
+                let decrypted_data = decrypt_with(data, identifier, &decrypted_keys, &embedded_keys)?;
+                Ok::<_, error_stack::Report<errors::CryptoError>>((hash_key, decrypted_data))
             })
             .collect::<errors::CustomResult<FxHashMap<String, DecryptedData>, errors::CryptoError>>(
             )
@@ -295,17 +409,21 @@ impl DataEncrypter<EncryptedData> for DecryptedData {
         self,
         state: &TenantState,
         identifier: &Identifier,
+        purpose: Option<&str>,
+        version: Option<Version>,
     ) -> errors::CustomResult<EncryptedData, errors::CryptoError> {
-        let version = Version::get_latest(identifier, state).await;
-        let decrypted_key = Key::get_key(state, identifier, version).await.switch()?;
+        let decrypted_key = encryption_key(state, identifier, version).await?;
 
-        let key = GcmAes256::new(decrypted_key.key)?;
+        let key = data_cipher(&decrypted_key, purpose)?;
 
         let encrypted_data = key.encrypt(self.inner())?;
 
         Ok(EncryptedData {
             version: decrypted_key.version,
             data: encrypted_data,
+            wrapped_key: None,
+            purpose: purpose.map(str::to_string),
+            shared_keys: Vec::new(),
         })
     }
 }
@@ -317,13 +435,252 @@ impl DataDecrypter<DecryptedData> for EncryptedData {
         state: &TenantState,
         identifier: &Identifier,
     ) -> errors::CustomResult<DecryptedData, errors::CryptoError> {
-        let version = self.version;
-        let decrypted_key = Key::get_key(state, identifier, version).await.switch()?;
-
-        let key = GcmAes256::new(decrypted_key.key)?;
+        let key = match EmbeddedKey::of(&self, identifier)? {
+            Some(embedded_key) => embedded_key.unwrap(state, identifier).await?,
+            None => {
+                let version = self.version;
+                let decrypted_key = Key::get_key(state, identifier, version).await.switch()?;
+
+                data_cipher(&decrypted_key, self.purpose.as_deref())?
+            }
+        };
 
         let decrypted_data = key.decrypt(self.inner())?;
 
         Ok(DecryptedData::from_data(decrypted_data))
     }
 }
+
+/// Key generated for a single encryption request that is carried, wrapped, in every ciphertext
+/// of the request instead of being stored.
+pub struct ContentKey {
+    key: GcmAes256,
+    wrapped_key: Option<StrongSecret<Vec<u8>>>,
+    shared_keys: Vec<SharedKey>,
+}
+
+impl ContentKey {
+    const KEY_LENGTH: usize = 32;
+
+    fn generate() -> errors::CustomResult<StrongSecret<[u8; Self::KEY_LENGTH]>, errors::CryptoError>
+    {
+        use ring::rand::SecureRandom;
+
+        let mut key = [0_u8; Self::KEY_LENGTH];
+        ring::rand::SystemRandom::new().fill(&mut key).switch()?;
+        Ok(StrongSecret::new(key))
+    }
+
+    /// Identifier a `self_contained` key is wrapped with, each part prefixed with its length so
+    /// that no two identifiers are encoded alike
+    fn binding(identifier: &Identifier) -> Vec<u8> {
+        let (data_identifier, key_identifier) = identifier.get_identifier();
+        [data_identifier, key_identifier]
+            .iter()
+            .flat_map(|part| [part.len().to_be_bytes().as_slice(), part.as_bytes()].concat())
+            .collect()
+    }
+
+    /// Key of a `self_contained` ciphertext. The key is wrapped by the KMS together with the
+    /// identifier, so the ciphertext can only be decrypted with the KEK and under the identifier
+    /// it was encrypted for.
+    pub async fn self_contained(
+        state: &TenantState,
+        identifier: &Identifier,
+    ) -> errors::CustomResult<Self, errors::CryptoError> {
+        let key = Self::generate()?;
+        let wrapped_key = state
+            .keymanager_client
+            .encrypt_key(
+                [key.peek().as_slice(), Self::binding(identifier).as_slice()]
+                    .concat()
+                    .into(),
+            )
+            .await?;
+
+        Ok(Self {
+            key: GcmAes256::new(key)?,
+            wrapped_key: Some(wrapped_key),
+            shared_keys: Vec::new(),
+        })
+    }
+
+    /// Key of a ciphertext shared by several identifiers, wrapped by the latest data key version
+    /// of each of them
+    pub async fn shared(
+        state: &TenantState,
+        identifiers: &[Identifier],
+    ) -> errors::CustomResult<Self, errors::CryptoError> {
+        let key = Self::generate()?;
+
+        let futures = identifiers.iter().map(|identifier| {
+            let key = &key;
+            async move {
+                let version = Version::get_latest(identifier, state).await;
+                let data_key = Key::get_key(state, identifier, version).await.switch()?;
+                let wrapped_key =
+                    GcmAes256::new(data_key.key)?.encrypt(key.peek().to_vec().into())?;
+
+                Ok::<_, error_stack::Report<errors::CryptoError>>(SharedKey {
+                    identifier: identifier.clone(),
+                    version: data_key.version,
+                    wrapped_key: wrapped_key.peek().clone(),
+                })
+            }
+        });
+        let shared_keys = futures::future::try_join_all(futures).await?;
+
+        Ok(Self {
+            key: GcmAes256::new(key)?,
+            wrapped_key: None,
+            shared_keys,
+        })
+    }
+
+    pub fn encrypt(
+        &self,
+        data: DecryptedData,
+    ) -> errors::CustomResult<EncryptedData, errors::CryptoError> {
+        Ok(EncryptedData {
+            version: Version::default(),
+            data: self.key.encrypt(data.inner())?,
+            wrapped_key: self.wrapped_key.clone(),
+            purpose: None,
+            shared_keys: self.shared_keys.clone(),
+        })
+    }
+
+    pub fn encrypt_group(
+        &self,
+        group: DecryptedDataGroup,
+    ) -> errors::CustomResult<EncryptedDataGroup, errors::CryptoError> {
+        group
+            .0
+            .into_par_iter()
+            .map(|(hash_key, data)| Ok((hash_key, self.encrypt(data)?)))
+            .collect::<errors::CustomResult<FxHashMap<_, _>, _>>()
+            .map(EncryptedDataGroup)
+    }
+}
+
+/// Wrapped content key of a ciphertext, as seen by the identifier decrypting it
+#[derive(PartialEq, Eq, Hash)]
+enum EmbeddedKey {
+    /// Wrapped by the KMS
+    SelfContained(Vec<u8>),
+    /// Wrapped by a data key version of the identifier
+    Shared(Version, Vec<u8>),
+}
+
+impl EmbeddedKey {
+    /// `None` for data encrypted directly with a data key version
+    fn of(
+        data: &EncryptedData,
+        identifier: &Identifier,
+    ) -> errors::CustomResult<Option<Self>, errors::CryptoError> {
+        if let Some(wrapped_key) = &data.wrapped_key {
+            return Ok(Some(Self::SelfContained(wrapped_key.peek().clone())));
+        }
+        if data.shared_keys.is_empty() {
+            return Ok(None);
+        }
+
+        data.shared_keys
+            .iter()
+            .find(|shared_key| &shared_key.identifier == identifier)
+            .map(|shared_key| {
+                Some(Self::Shared(
+                    shared_key.version,
+                    shared_key.wrapped_key.clone(),
+                ))
+            })
+            .ok_or_else(|| errors::CryptoError::DecryptionFailed("AES").into_report())
+    }
+
+    async fn unwrap(
+        &self,
+        state: &TenantState,
+        identifier: &Identifier,
+    ) -> errors::CustomResult<GcmAes256, errors::CryptoError> {
+        let key = match self {
+            Self::SelfContained(wrapped_key) => {
+                let unwrapped = state
+                    .keymanager_client
+                    .decrypt_key(wrapped_key.clone().into())
+                    .await?;
+
+                let (key, bound_identifier) = unwrapped
+                    .peek()
+                    .split_at_checked(ContentKey::KEY_LENGTH)
+                    .ok_or(errors::CryptoError::DecryptionFailed("KMS"))?;
+                error_stack::ensure!(
+                    bound_identifier == ContentKey::binding(identifier),
+                    errors::CryptoError::DecryptionFailed("KMS")
+                );
+                key.to_vec()
+            }
+            Self::Shared(version, wrapped_key) => {
+                let data_key = Key::get_key(state, identifier, *version).await.switch()?;
+                GcmAes256::new(data_key.key)?
+                    .decrypt(wrapped_key.clone().into())?
+                    .peek()
+                    .clone()
+            }
+        };
+
+        let key = <[u8; 32]>::try_from(key)
+            .map_err(|_| errors::CryptoError::DecryptionFailed("AES").into_report())?;
+        GcmAes256::new(key.into())
+    }
+}
+
+/// Unwraps the distinct content keys embedded in the ciphertexts
+async fn unwrap_embedded_keys<'a>(
+    state: &TenantState,
+    identifier: &Identifier,
+    data: impl Iterator<Item = &'a EncryptedData>,
+) -> errors::CustomResult<FxHashMap<EmbeddedKey, GcmAes256>, errors::CryptoError> {
+    let embedded_keys = data
+        .map(|data| EmbeddedKey::of(data, identifier))
+        .filter_map(Result::transpose)
+        .collect::<errors::CustomResult<FxHashSet<_>, _>>()?;
+
+    let futures = embedded_keys.into_iter().map(|embedded_key| async move {
+        let key = embedded_key.unwrap(state, identifier).await?;
+        Ok::<_, error_stack::Report<errors::CryptoError>>((embedded_key, key))
+    });
+
+    Ok(FxHashMap::from_iter(
+        futures::future::try_join_all(futures).await?,
+    ))
+}
+
+fn decrypt_with(
+    data: EncryptedData,
+    identifier: &Identifier,
+    managed_keys: &FxHashMap<Version, Key>,
+    embedded_keys: &FxHashMap<EmbeddedKey, GcmAes256>,
+) -> errors::CustomResult<DecryptedData, errors::CryptoError> {
+    let key = match EmbeddedKey::of(&data, identifier)? {
+        Some(embedded_key) => embedded_keys.get(&embedded_key).cloned(),
+        None => managed_keys
+            .get(&data.version)
+            .map(|decrypted_key| data_cipher(decrypted_key, data.purpose.as_deref()))
+            .transpose()?,
+    }
+    .ok_or_else(|| errors::CryptoError::DecryptionFailed("AES").into_report())?;
+
+    let decrypted_data = key.decrypt(data.inner())?;
+    Ok(DecryptedData::from_data(decrypted_data))
+}
+
+/// Cipher of a data key version, or of its sub-key when a purpose is given
+fn data_cipher(
+    key: &Key,
+    purpose: Option<&str>,
+) -> errors::CustomResult<GcmAes256, errors::CryptoError> {
+    match purpose {
+        Some(purpose) => GcmAes256::new(purpose::derive_key(&key.key, purpose)?),
+        None => GcmAes256::new(key.key.clone()),
+    }
+}
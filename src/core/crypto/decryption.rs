@@ -12,6 +12,17 @@ pub(super) async fn decryption(
     state: TenantState,
     req: DecryptionRequest,
 ) -> errors::CustomResult<DecryptionResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    if let Some(purpose) = &req.purpose {
+        error_stack::ensure!(
+            req.data.is_for_purpose(purpose),
+            errors::ApplicationErrorResponse::ParsingFailed(
+                "Data was not encrypted for the requested purpose".to_string()
+            )
+        );
+    }
+
     let identifier = req.identifier.clone();
     let decrypted_data = req
         .data
@@ -1,22 +1,141 @@
 use opentelemetry::KeyValue;
 
 use crate::{
+    core::{crypto::ContentKey, datakey::create::generate_and_create_data_key},
+    crypto::purpose,
     env::observability as logger,
     errors::{self, SwitchError},
     metrics,
     multitenancy::TenantState,
-    types::{requests::EncryptDataRequest, response::EncryptionResponse},
+    storage::dek::DataKeyStorageInterface,
+    types::{
+        Identifier, Key,
+        key::Version,
+        requests::{CreateDataKeyRequest, EncryptDataRequest, EncryptionMode},
+        response::EncryptionResponse,
+    },
 };
 
+/// Creates the first data key of an identifier that has none, when the tenant provisions keys of
+/// its data identifier on first encrypt. Concurrent provisioning of the same identifier settles on
+/// a single key through `get_or_insert_data_key`.
+async fn provision_data_key(
+    state: &TenantState,
+    identifier: &Identifier,
+) -> errors::CustomResult<(), errors::ApplicationErrorResponse> {
+    if !state.auto_provision.applies_to(identifier) {
+        return Ok(());
+    }
+
+    let version = Version::get_latest(identifier, state).await;
+    match Key::get_key(state, identifier, version).await {
+        Err(err) if matches!(err.current_context(), errors::DatabaseError::NotFound) => {
+            logger::info!(provisioned_identifier = %identifier);
+            generate_and_create_data_key(
+                state.clone(),
+                CreateDataKeyRequest {
+                    identifier: identifier.clone(),
+                    parent: None,
+                },
+            )
+            .await?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
 pub(super) async fn encryption(
     state: TenantState,
     req: EncryptDataRequest,
 ) -> errors::CustomResult<EncryptionResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    if let Some(purpose) = &req.purpose {
+        error_stack::ensure!(
+            purpose::is_valid(purpose),
+            errors::ApplicationErrorResponse::ParsingFailed("Invalid purpose".to_string())
+        );
+        error_stack::ensure!(
+            req.mode == EncryptionMode::Managed && req.shared_with.is_empty(),
+            errors::ApplicationErrorResponse::ParsingFailed(
+                "A purpose can only be used with managed encryption for a single identifier"
+                    .to_string()
+            )
+        );
+    }
+    if !req.shared_with.is_empty() {
+        error_stack::ensure!(
+            req.mode == EncryptionMode::Managed,
+            errors::ApplicationErrorResponse::ParsingFailed(
+                "Self contained data can not be shared".to_string()
+            )
+        );
+        req.shared_with
+            .iter()
+            .try_for_each(|identifier| state.validate_identifier(identifier))?;
+    }
+
+    if let Some(key_version) = req.key_version {
+        error_stack::ensure!(
+            req.mode == EncryptionMode::Managed && req.shared_with.is_empty(),
+            errors::ApplicationErrorResponse::ParsingFailed(
+                "A key version can only be pinned with managed encryption for a single identifier"
+                    .to_string()
+            )
+        );
+        // Read from the primary, a version deactivated by a rollback may still be active on a
+        // lagging replica
+        let active_versions = state
+            .get_db_pool()
+            .get_active_versions(&req.identifier)
+            .await
+            .switch()?;
+        error_stack::ensure!(
+            active_versions.contains(&key_version),
+            errors::ApplicationErrorResponse::ParsingFailed(format!(
+                "Key version {key_version} is not active"
+            ))
+        );
+    }
+
+    if req.mode == EncryptionMode::Managed {
+        provision_data_key(&state, &req.identifier).await?;
+        for shared_with in &req.shared_with {
+            provision_data_key(&state, shared_with).await?;
+        }
+    }
+
     let identifier = req.identifier.clone();
-    let encrypted_data = req
-        .data
-        .encrypt(&state, &identifier)
-        .await
+    let encrypted_data = match req.mode {
+        EncryptionMode::Managed if req.shared_with.is_empty() => {
+            req.data
+                .encrypt(&state, &identifier, req.purpose.as_deref(), req.key_version)
+                .await
+        }
+        EncryptionMode::Managed => {
+            let mut identifiers = vec![identifier.clone()];
+            for shared_with in req.shared_with {
+                if !identifiers.contains(&shared_with) {
+                    identifiers.push(shared_with);
+                }
+            }
+
+            async {
+                let key = ContentKey::shared(&state, &identifiers).await?;
+                req.data.encrypt_with_content_key(&state, &key)
+            }
+            .await
+        }
+        EncryptionMode::SelfContained => {
+            async {
+                let key = ContentKey::self_contained(&state, &identifier).await?;
+                req.data.encrypt_with_content_key(&state, &key)
+            }
+            .await
+        }
+    };
+    let encrypted_data = encrypted_data
         .map_err(|err| {
             logger::error!(encryption_error=?err);
 
@@ -0,0 +1,50 @@
+use error_stack::IntoReport;
+use opentelemetry::KeyValue;
+
+use crate::{
+    core::crypto::DataDecrypter,
+    env::observability as logger,
+    errors::{self, SwitchError},
+    metrics,
+    multitenancy::TenantState,
+    types::{requests::ReEncryptDataRequest, response::ReEncryptionResponse},
+};
+
+pub(super) async fn reencryption(
+    state: TenantState,
+    req: ReEncryptDataRequest,
+) -> errors::CustomResult<ReEncryptionResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let recipient = state
+        .recipients
+        .get(&req.recipient)
+        .ok_or(errors::ApplicationErrorResponse::NotFound("recipient registry").into_report())?;
+
+    let identifier = req.identifier.clone();
+    let reencrypted_data = async {
+        let decrypted_data = req.data.decrypt(&state, &identifier).await?;
+        recipient.seal(decrypted_data.inner())
+    }
+    .await
+    .map_err(|err| {
+        logger::error!(reencryption_error=?err);
+
+        let (data_identifier, key_identifier) = identifier.get_identifier();
+        metrics::REENCRYPTION_FAILURE.add(
+            1,
+            &[
+                KeyValue::new("key_identifier", key_identifier),
+                KeyValue::new("data_identifier", data_identifier),
+            ],
+        );
+        err
+    })
+    .switch()?;
+
+    Ok(ReEncryptionResponse {
+        recipient: req.recipient,
+        algorithm: recipient.algorithm,
+        data: reencrypted_data,
+    })
+}
@@ -1,19 +1,36 @@
 pub mod create;
+mod lease;
+mod rollback;
 mod rotate;
+mod tr31;
 mod transfer;
+mod wrap;
 
-use axum::Json;
+use axum::{Extension, Json};
 use opentelemetry::KeyValue;
 
-use self::{create::*, rotate::*};
+use self::{create::*, lease::*, rollback::*, rotate::*, tr31::*, wrap::*};
 use crate::{
+    app::PeerCertificate,
+    core::crypto::KeyDecrypter,
+    crypto::kcv,
     env::observability as logger,
-    errors::{self, ToContainerError},
+    errors::{self, SwitchError, ToContainerError},
     metrics,
     multitenancy::TenantState,
+    storage::types::DataKey,
     types::{
-        requests::{CreateDataKeyRequest, RotateDataKeyRequest, TransferKeyRequest},
-        response::DataKeyCreateResponse,
+        requests::{
+            CreateDataKeyRequest, CreateWrappingKeyRequest, ExportKeyBlockRequest,
+            ImportDataKeyRequest, ImportKeyBlockRequest, LeaseDataKeyRequest,
+            RollbackDataKeyRequest, RotateDataKeyRequest, TransferKeyRequest, UnwrapKeyRequest,
+            WrapKeyRequest,
+        },
+        response::{
+            DataKeyCreateResponse, DataKeyRollbackResponse, DataKeyRotateResponse,
+            ExportKeyBlockResponse, ImportKeyBlockResponse, LeaseDataKeyResponse,
+            UnwrapKeyResponse, WrapKeyResponse, WrappingKeyResponse,
+        },
     },
 };
 
@@ -45,7 +62,7 @@ pub async fn create_data_key(
 pub async fn rotate_data_key(
     state: TenantState,
     Json(req): Json<RotateDataKeyRequest>,
-) -> errors::ApiResponseResult<Json<DataKeyCreateResponse>> {
+) -> errors::ApiResponseResult<Json<DataKeyRotateResponse>> {
     let identifier = req.identifier.clone();
 
     generate_and_rotate_data_key(state, req)
@@ -67,6 +84,16 @@ pub async fn rotate_data_key(
         .to_container_error()
 }
 
+pub async fn rollback_data_key(
+    state: TenantState,
+    Json(req): Json<RollbackDataKeyRequest>,
+) -> errors::ApiResponseResult<Json<DataKeyRollbackResponse>> {
+    rollback_latest_data_key(state, req)
+        .await
+        .map(Json)
+        .to_container_error()
+}
+
 pub async fn transfer_data_key(
     state: TenantState,
     Json(req): Json<TransferKeyRequest>,
@@ -76,3 +103,81 @@ pub async fn transfer_data_key(
         .map(Json)
         .to_container_error()
 }
+
+pub async fn create_import_wrapping_key(
+    state: TenantState,
+    Json(req): Json<CreateWrappingKeyRequest>,
+) -> errors::ApiResponseResult<Json<WrappingKeyResponse>> {
+    transfer::create_wrapping_key(state, req)
+        .await
+        .map(Json)
+        .to_container_error()
+}
+
+pub async fn import_data_key(
+    state: TenantState,
+    Json(req): Json<ImportDataKeyRequest>,
+) -> errors::ApiResponseResult<Json<DataKeyCreateResponse>> {
+    transfer::import_wrapped_data_key(state, req)
+        .await
+        .map(Json)
+        .to_container_error()
+}
+
+pub async fn lease_data_key(
+    state: TenantState,
+    peer_certificate: Option<Extension<PeerCertificate>>,
+    Json(req): Json<LeaseDataKeyRequest>,
+) -> errors::ApiResponseResult<Json<LeaseDataKeyResponse>> {
+    generate_data_key_lease(state, req, peer_certificate.map(|Extension(peer)| peer))
+        .await
+        .map(Json)
+        .to_container_error()
+}
+
+pub async fn wrap_data_key(
+    state: TenantState,
+    Json(req): Json<WrapKeyRequest>,
+) -> errors::ApiResponseResult<Json<WrapKeyResponse>> {
+    wrap_key(state, req).await.map(Json).to_container_error()
+}
+
+pub async fn unwrap_data_key(
+    state: TenantState,
+    Json(req): Json<UnwrapKeyRequest>,
+) -> errors::ApiResponseResult<Json<UnwrapKeyResponse>> {
+    unwrap_key(state, req).await.map(Json).to_container_error()
+}
+
+pub async fn import_tr31_key_block(
+    state: TenantState,
+    Json(req): Json<ImportKeyBlockRequest>,
+) -> errors::ApiResponseResult<Json<ImportKeyBlockResponse>> {
+    import_key_block(state, req)
+        .await
+        .map(Json)
+        .to_container_error()
+}
+
+pub async fn export_tr31_key_block(
+    state: TenantState,
+    Json(req): Json<ExportKeyBlockRequest>,
+) -> errors::ApiResponseResult<Json<ExportKeyBlockResponse>> {
+    export_key_block(state, req)
+        .await
+        .map(Json)
+        .to_container_error()
+}
+
+/// KCV of the stored key. Keys stored before KCVs were persisted are decrypted to compute it.
+async fn key_check_value(
+    state: &TenantState,
+    data_key: DataKey,
+) -> errors::CustomResult<String, errors::ApplicationErrorResponse> {
+    if let Some(kcv) = data_key.kcv {
+        return Ok(kcv);
+    }
+
+    let key = data_key.decrypt(state).await.switch()?;
+    kcv::compute(&key.key).switch()
+}
@@ -4,16 +4,41 @@ use crate::{
     errors::{self, SwitchError},
     multitenancy::TenantState,
     storage::dek::DataKeyStorageInterface,
-    types::{Key, key::Version, requests::CreateDataKeyRequest, response::DataKeyCreateResponse},
+    types::{
+        Key,
+        key::{ParentKey, Version},
+        requests::CreateDataKeyRequest,
+        response::DataKeyCreateResponse,
+    },
 };
 
 pub async fn generate_and_create_data_key(
     state: TenantState,
     req: CreateDataKeyRequest,
 ) -> errors::CustomResult<DataKeyCreateResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
     let db = state.get_db_pool();
     let version = Version::get_latest(&req.identifier, &state).await;
 
+    let parent = match req.parent {
+        Some(parent) => {
+            state.validate_identifier(&parent)?;
+            error_stack::ensure!(
+                parent != req.identifier,
+                errors::ApplicationErrorResponse::ParsingFailed(
+                    "An identifier can not be its own parent".to_string()
+                )
+            );
+
+            Some(ParentKey {
+                version: db.get_latest_version(&parent).await.switch()?,
+                identifier: parent,
+            })
+        }
+        None => None,
+    };
+
     let (source, aes_key) = state.keymanager_client.generate_key().await.switch()?;
 
     let key = Key {
@@ -21,6 +46,7 @@ pub async fn generate_and_create_data_key(
         identifier: req.identifier.clone(),
         key: aes_key,
         source,
+        parent,
     }
     .encrypt(&state)
     .await
@@ -31,8 +57,10 @@ pub async fn generate_and_create_data_key(
     })?;
 
     let data_key = db.get_or_insert_data_key(key).await.switch()?;
+    let key_version = data_key.version;
     Ok(DataKeyCreateResponse {
-        key_version: data_key.version,
+        key_version,
+        kcv: super::key_check_value(&state, data_key).await?,
         identifier: req.identifier,
     })
 }
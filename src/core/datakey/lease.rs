@@ -0,0 +1,64 @@
+use base64::Engine;
+use error_stack::IntoReport;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+
+use crate::{
+    app::PeerCertificate,
+    consts::base64::BASE64_ENGINE,
+    errors::{self, SwitchError},
+    multitenancy::TenantState,
+    types::{Key, key::Version, requests::LeaseDataKeyRequest, response::LeaseDataKeyResponse},
+};
+
+pub async fn generate_data_key_lease(
+    state: TenantState,
+    req: LeaseDataKeyRequest,
+    peer_certificate: Option<PeerCertificate>,
+) -> errors::CustomResult<LeaseDataKeyResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let ttl = req
+        .ttl
+        .unwrap_or(state.lease.max_ttl)
+        .min(state.lease.max_ttl);
+    let max_uses = req
+        .max_uses
+        .unwrap_or(state.lease.max_uses)
+        .min(state.lease.max_uses);
+
+    let version = Version::get_latest(&req.identifier, &state).await;
+    let key = Key::get_key(&state, &req.identifier, version)
+        .await
+        .switch()?;
+
+    let leased_key = match req.wrap_with {
+        Some(recipient) => state
+            .recipients
+            .get(&recipient)
+            .ok_or(errors::ApplicationErrorResponse::NotFound("recipient registry").into_report())?
+            .seal(StrongSecret::new(key.key.peek().to_vec()))
+            .switch()?,
+        None => {
+            error_stack::ensure!(
+                peer_certificate.is_some(),
+                errors::ApplicationErrorResponse::ParsingFailed(
+                    "Plaintext data keys are only leased to clients authenticated over mTLS, provide wrap_with"
+                        .to_string()
+                )
+            );
+            BASE64_ENGINE.encode(key.key.peek())
+        }
+    };
+
+    let expires_at = time::OffsetDateTime::now_utc()
+        .unix_timestamp()
+        .saturating_add(i64::try_from(ttl).unwrap_or(i64::MAX));
+
+    Ok(LeaseDataKeyResponse {
+        identifier: req.identifier,
+        key_version: key.version,
+        key: leased_key.into(),
+        expires_at,
+        max_uses,
+    })
+}
@@ -0,0 +1,54 @@
+use error_stack::IntoReport;
+
+use crate::{
+    env::observability as logger,
+    errors::{self, SwitchError},
+    multitenancy::TenantState,
+    storage::{cache, dek::DataKeyStorageInterface},
+    types::{key::Version, requests::RollbackDataKeyRequest, response::DataKeyRollbackResponse},
+};
+
+/// Deactivates the latest active version of the identifier, so that data is encrypted with the
+/// previous active version again. Data encrypted with the deactivated version stays decryptable.
+/// Responds once the other instances had the time to poll the rollback and drop their cached
+/// latest version.
+pub async fn rollback_latest_data_key(
+    state: TenantState,
+    req: RollbackDataKeyRequest,
+) -> errors::CustomResult<DataKeyRollbackResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let db = state.get_db_pool();
+    let newest_version = db.get_newest_version(&req.identifier).await.switch()?;
+    let active_versions = db.get_active_versions(&req.identifier).await.switch()?;
+    let (latest_version, previous_version) = match active_versions.as_slice() {
+        [latest, previous, ..] => (*latest, *previous),
+        [] => return Err(errors::DatabaseError::NotFound.into_report()).switch(),
+        [_] => {
+            return Err(errors::ApplicationErrorResponse::ParsingFailed(
+                "The only active version of a data key can not be rolled back".to_string(),
+            )
+            .into_report());
+        }
+    };
+    let data_key = db
+        .get_key(previous_version, &req.identifier)
+        .await
+        .switch()?;
+
+    // Serialized with rotations, which would otherwise leave a version newer than the rolled
+    // back one active. A concurrent rotation or rollback fails it as a unique violation.
+    db.rollback_version(&req.identifier, newest_version, latest_version)
+        .await
+        .switch()?;
+    Version::invalidate_latest(&req.identifier, &state).await;
+    logger::info!(rolled_back = %req.identifier, version = %latest_version, active_version = %previous_version);
+    tokio::time::sleep(2 * cache::ROLLBACK_POLL_INTERVAL).await;
+
+    Ok(DataKeyRollbackResponse {
+        key_version: previous_version,
+        kcv: super::key_check_value(&state, data_key).await?,
+        identifier: req.identifier,
+        rolled_back_version: latest_version,
+    })
+}
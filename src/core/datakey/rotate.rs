@@ -1,43 +1,116 @@
+use rustc_hash::FxHashSet;
+
 use crate::{
     core::crypto::KeyEncrypter,
     env::observability as logger,
     errors::{self, SwitchError},
     multitenancy::TenantState,
-    storage::dek::DataKeyStorageInterface,
-    types::{Key, requests::RotateDataKeyRequest, response::DataKeyCreateResponse},
+    storage::{dek::DataKeyStorageInterface, types::DataKey},
+    types::{
+        Identifier, Key,
+        key::{ParentKey, Version},
+        requests::RotateDataKeyRequest,
+        response::DataKeyRotateResponse,
+    },
 };
 
 pub async fn generate_and_rotate_data_key(
     state: TenantState,
     req: RotateDataKeyRequest,
-) -> errors::CustomResult<DataKeyCreateResponse, errors::ApplicationErrorResponse> {
+) -> errors::CustomResult<DataKeyRotateResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let db = state.get_db_pool();
+    let (data_key, created) = rotate(&state, &req.identifier).await?;
+
+    // Descendants are rotated after their parent, so that their new versions are wrapped by the
+    // parent's new version. Each rotation commits on its own, a failed descendant is reported and
+    // rotating it again resumes the cascade below it.
+    let mut visited = FxHashSet::from_iter([req.identifier.to_string()]);
+    let mut rotated_children = Vec::new();
+    let mut failed_children = Vec::new();
+    let mut pending = db.get_children(&req.identifier).await.switch()?;
+    while let Some(child) = pending.pop() {
+        if !visited.insert(child.to_string()) {
+            continue;
+        }
+        let children = match rotate(&state, &child).await {
+            Ok((child_key, created)) => {
+                logger::info!(rotated_child = %child, version = %child_key.version, created);
+                db.get_children(&child).await.switch()
+            }
+            Err(err) => Err(err),
+        };
+        match children {
+            Ok(children) => {
+                pending.extend(children);
+                rotated_children.push(child);
+            }
+            Err(err) => {
+                logger::error!(failed_child = %child, ?err);
+                failed_children.push(child);
+            }
+        }
+    }
+
+    let key_version = data_key.version;
+    Ok(DataKeyRotateResponse {
+        key_version,
+        kcv: super::key_check_value(&state, data_key).await?,
+        identifier: req.identifier,
+        created,
+        rotated_children,
+        failed_children,
+    })
+}
+
+/// Creates the next version of the identifier's data key. Keys with a parent are wrapped by the
+/// parent's latest version. Rotations of an identifier are serialized by the storage, the flag is
+/// `false` when a concurrent rotation already created the next version.
+async fn rotate(
+    state: &TenantState,
+    identifier: &Identifier,
+) -> errors::CustomResult<(DataKey, bool), errors::ApplicationErrorResponse> {
     let db = state.get_db_pool();
-    let version = db
-        .get_latest_version(&req.identifier)
+    let newest_version = db.get_newest_version(identifier).await.switch()?;
+    let version = newest_version.increment().switch()?;
+    let parent = match db
+        .get_key(newest_version, identifier)
         .await
         .switch()?
-        .increment()
-        .switch()?;
+        .parent()
+        .switch()?
+    {
+        Some(parent) => Some(ParentKey {
+            version: db.get_latest_version(&parent.identifier).await.switch()?,
+            identifier: parent.identifier,
+        }),
+        None => None,
+    };
 
+    // Generated before the storage serializes the rotation, so that no connection or lock is
+    // held across the key manager round trip. A racing rotation's key is discarded.
     let (source, aes_key) = state.keymanager_client.generate_key().await.switch()?;
-
-    let key = Key {
+    let new = Key {
         version,
-        identifier: req.identifier.clone(),
+        identifier: identifier.clone(),
         key: aes_key,
         source,
+        parent,
     }
-    .encrypt(&state)
+    .encrypt(state)
     .await
-    .switch()
     .map_err(|err| {
         logger::error!(?err);
         err
-    })?;
-
-    let data_key = db.get_or_insert_data_key(key).await.switch()?;
-    Ok(DataKeyCreateResponse {
-        key_version: data_key.version,
-        identifier: req.identifier,
     })
+    .switch()?;
+
+    let rotated = db
+        .rotate_data_key(identifier, newest_version, new)
+        .await
+        .switch()?;
+    Version::invalidate_latest(identifier, state).await;
+
+    Ok(rotated)
 }
@@ -0,0 +1,158 @@
+use error_stack::{IntoReport, ResultExt};
+use hyperswitch_masking::PeekInterface;
+
+use crate::{
+    core::crypto::{KeyDecrypter, KeyEncrypter},
+    crypto::tr31::{self, KeyBlockAttributes},
+    env::observability as logger,
+    errors::{self, SwitchError},
+    multitenancy::TenantState,
+    storage::dek::DataKeyStorageInterface,
+    types::{
+        Key,
+        key::{KeyMetadata, Version},
+        requests::{ExportKeyBlockRequest, ImportKeyBlockRequest},
+        response::{ExportKeyBlockResponse, ImportKeyBlockResponse},
+    },
+};
+
+pub async fn import_key_block(
+    state: TenantState,
+    req: ImportKeyBlockRequest,
+) -> errors::CustomResult<ImportKeyBlockResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+    state.validate_identifier(&req.kbpk.identifier)?;
+
+    let db = state.get_db_pool();
+    let kbpk = Key::get_key(&state, &req.kbpk.identifier, req.kbpk.key_version)
+        .await
+        .switch()?;
+
+    let (attributes, key) = tr31::import(&kbpk.key, req.key_block.peek())
+        .map_err(|err| {
+            logger::error!(key_block_import_error=?err);
+            err
+        })
+        .change_context(errors::ApplicationErrorResponse::ParsingFailed(
+            "Failed to import the TR-31 key block".to_string(),
+        ))?;
+
+    let key = <[u8; 32]>::try_from(key.peek().as_slice())
+        .ok()
+        .filter(|_| attributes.is_data_key())
+        .ok_or_else(|| {
+            errors::ApplicationErrorResponse::ParsingFailed(
+                "Only AES-256 keys for data encryption and decryption can be imported".to_string(),
+            )
+            .into_report()
+        })?;
+
+    let version = super::transfer::next_version(&state, &req.identifier).await?;
+
+    let metadata = serde_json::to_string(&KeyMetadata {
+        key_block: Some(attributes.clone()),
+        ..Default::default()
+    })
+    .change_context(errors::ApplicationErrorResponse::InternalServerError(
+        "Failed to serialize the key metadata",
+    ))?;
+
+    let mut data_key = Key {
+        version,
+        identifier: req.identifier.clone(),
+        key: key.into(),
+        source: state.keymanager_client.source(),
+        parent: None,
+    }
+    .encrypt(&state)
+    .await
+    .switch()
+    .map_err(|err| {
+        logger::error!(?err);
+        err
+    })?;
+    data_key.key_metadata = Some(metadata);
+
+    let data_key = db.get_or_insert_data_key(data_key).await.switch()?;
+    let key_version = data_key.version;
+
+    Ok(ImportKeyBlockResponse {
+        identifier: req.identifier,
+        key_version,
+        kcv: super::key_check_value(&state, data_key).await?,
+        attributes,
+    })
+}
+
+pub async fn export_key_block(
+    state: TenantState,
+    req: ExportKeyBlockRequest,
+) -> errors::CustomResult<ExportKeyBlockResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+    state.validate_identifier(&req.kbpk.identifier)?;
+
+    let db = state.get_db_pool();
+    let version = match req.key_version {
+        Some(version) => version,
+        None => Version::get_latest(&req.identifier, &state).await,
+    };
+
+    let mut data_key = db.get_key(version, &req.identifier).await.switch()?;
+    let metadata = data_key
+        .key_metadata
+        .take()
+        .map(|metadata| serde_json::from_str::<KeyMetadata>(&metadata))
+        .transpose()
+        .change_context(errors::ApplicationErrorResponse::InternalServerError(
+            "Failed to parse the key metadata",
+        ))?
+        .unwrap_or_default();
+
+    let attributes = match metadata.key_block {
+        Some(attributes) => attributes,
+        None => {
+            // Keys which were not imported carry no exportability of their own
+            error_stack::ensure!(
+                state.key_block.export_generated_keys,
+                errors::ApplicationErrorResponse::ParsingFailed(
+                    "Only keys imported from a key block can be exported".to_string()
+                )
+            );
+            let data_key_attributes = KeyBlockAttributes::default();
+            let attributes = req.attributes.unwrap_or_default();
+            error_stack::ensure!(
+                data_key_attributes.permits(&attributes),
+                errors::ApplicationErrorResponse::ParsingFailed(
+                    "Data keys can only be exported as AES data encryption keys".to_string()
+                )
+            );
+            attributes
+        }
+    };
+    error_stack::ensure!(
+        attributes.is_exportable(),
+        errors::ApplicationErrorResponse::ParsingFailed(
+            "The key is marked as non-exportable".to_string()
+        )
+    );
+
+    let key = data_key.decrypt(&state).await.switch()?;
+    let kbpk = Key::get_key(&state, &req.kbpk.identifier, req.kbpk.key_version)
+        .await
+        .switch()?;
+
+    let key_block = tr31::export(&kbpk.key, &attributes, key.key.peek())
+        .map_err(|err| {
+            logger::error!(key_block_export_error=?err);
+            err
+        })
+        .change_context(errors::ApplicationErrorResponse::ParsingFailed(
+            "Invalid key block attributes".to_string(),
+        ))?;
+
+    Ok(ExportKeyBlockResponse {
+        identifier: req.identifier,
+        key_version: key.version,
+        key_block,
+    })
+}
@@ -1,22 +1,31 @@
 use base64::Engine;
 use error_stack::{IntoReport, ResultExt};
-use hyperswitch_masking::PeekInterface;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
 
 use crate::{
     consts::base64::BASE64_ENGINE,
     core::crypto::KeyEncrypter,
-    crypto::Source,
+    crypto::{Source, kcv, wrapping_key::ImportToken},
     env::observability as logger,
     errors::{self, SwitchError},
     multitenancy::TenantState,
-    storage::dek::DataKeyStorageInterface,
-    types::{Key, key::Version, requests::TransferKeyRequest, response::DataKeyCreateResponse},
+    storage::{dek::DataKeyStorageInterface, types::DataKey},
+    types::{
+        Identifier, Key,
+        key::{KeyMetadata, Version},
+        requests::{CreateWrappingKeyRequest, ImportDataKeyRequest, TransferKeyRequest},
+        response::{DataKeyCreateResponse, WrappingKeyResponse},
+    },
 };
 
+const IMPORT_TOKEN_TTL_SECS: i64 = 15 * 60;
+
 pub async fn transfer_data_key(
     state: TenantState,
     req: TransferKeyRequest,
 ) -> errors::CustomResult<DataKeyCreateResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
     let db = state.get_db_pool();
     let key = BASE64_ENGINE.decode(req.key.peek()).change_context(
         errors::ApplicationErrorResponse::InternalServerError("Failed to decode the base64 key"),
@@ -29,6 +38,7 @@ pub async fn transfer_data_key(
         identifier: req.identifier.clone(),
         key: key.into(),
         source: Source::KMS,
+        parent: None,
     }
     .encrypt(&state)
     .await
@@ -39,9 +49,216 @@ pub async fn transfer_data_key(
     })?;
 
     let data_key = db.get_or_insert_data_key(key).await.switch()?;
+    let key_version = data_key.version;
 
     Ok(DataKeyCreateResponse {
         identifier: req.identifier,
-        key_version: data_key.version,
+        key_version,
+        kcv: super::key_check_value(&state, data_key).await?,
     })
 }
+
+pub async fn create_wrapping_key(
+    state: TenantState,
+    req: CreateWrappingKeyRequest,
+) -> errors::CustomResult<WrappingKeyResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let key_version = match req.key_version {
+        Some(version) => version,
+        None => next_version(&state, &req.identifier).await?,
+    };
+    error_stack::ensure!(
+        key_version.inner() > 0,
+        errors::ApplicationErrorResponse::ParsingFailed("Invalid key version".to_string())
+    );
+
+    let key_pair = req.algorithm.generate().switch()?;
+    let expires_at = time::OffsetDateTime::now_utc().unix_timestamp() + IMPORT_TOKEN_TTL_SECS;
+
+    let import_token = ImportToken {
+        id: ImportToken::generate_id().switch()?,
+        algorithm: req.algorithm,
+        expires_at,
+        identifier: req.identifier.to_string(),
+        key_version: key_version.inner(),
+        private_key: key_pair.private_key,
+    };
+    let import_token = state
+        .keymanager_client
+        .encrypt_key(import_token.to_bytes().switch()?)
+        .await
+        .switch()?;
+
+    Ok(WrappingKeyResponse {
+        identifier: req.identifier,
+        key_version,
+        algorithm: req.algorithm,
+        public_key: BASE64_ENGINE.encode(key_pair.public_key),
+        import_token: BASE64_ENGINE.encode(import_token.peek()),
+        expires_at,
+    })
+}
+
+pub async fn import_wrapped_data_key(
+    state: TenantState,
+    req: ImportDataKeyRequest,
+) -> errors::CustomResult<DataKeyCreateResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    // The imported key is encrypted by the key manager of this deployment, so it can only be
+    // recorded under that source
+    let source = state.keymanager_client.source();
+    if let Some(requested) = &req.source {
+        error_stack::ensure!(
+            requested.to_string() == source.to_string(),
+            errors::ApplicationErrorResponse::ParsingFailed(format!(
+                "Keys of source {requested} can not be decrypted by this deployment, which uses {source}"
+            ))
+        );
+    }
+
+    let db = state.get_db_pool();
+    let import_token = BASE64_ENGINE.decode(&req.import_token).change_context(
+        errors::ApplicationErrorResponse::ParsingFailed(
+            "Failed to decode the base64 import token".to_string(),
+        ),
+    )?;
+    let wrapped_key = BASE64_ENGINE.decode(&req.wrapped_key).change_context(
+        errors::ApplicationErrorResponse::ParsingFailed(
+            "Failed to decode the base64 wrapped key".to_string(),
+        ),
+    )?;
+
+    let import_token = state
+        .keymanager_client
+        .decrypt_key(import_token.into())
+        .await
+        .and_then(|token| ImportToken::from_bytes(token.peek()))
+        .change_context(errors::ApplicationErrorResponse::ParsingFailed(
+            "Invalid import token".to_string(),
+        ))?;
+    error_stack::ensure!(
+        import_token.expires_at > time::OffsetDateTime::now_utc().unix_timestamp(),
+        errors::ApplicationErrorResponse::ParsingFailed("The import token has expired".to_string())
+    );
+    error_stack::ensure!(
+        import_token.identifier == req.identifier.to_string(),
+        errors::ApplicationErrorResponse::ParsingFailed(
+            "The import token was issued for a different identifier".to_string()
+        )
+    );
+
+    let key = import_token
+        .algorithm
+        .unwrap(&import_token.private_key, &wrapped_key)
+        .map_err(|err| {
+            logger::error!(key_import_error=?err);
+            err
+        })
+        .change_context(errors::ApplicationErrorResponse::ParsingFailed(
+            "Failed to unwrap the key".to_string(),
+        ))?;
+    let key = <[u8; 32]>::try_from(key.peek().as_slice())
+        .map(StrongSecret::new)
+        .map_err(|_| {
+            errors::ApplicationErrorResponse::ParsingFailed(
+                "Only AES-256 keys can be imported as data keys".to_string(),
+            )
+            .into_report()
+        })?;
+    error_stack::ensure!(
+        kcv::verify(&key, &req.kcv).switch()?,
+        errors::ApplicationErrorResponse::ParsingFailed(
+            "Key check value does not match the imported key".to_string()
+        )
+    );
+
+    // A token imports a single key under the version it was issued for. Retrying the import
+    // that used the token returns the stored key, any other key is rejected.
+    let version = Version::from(import_token.key_version);
+    let token_id = hex::encode(import_token.id);
+    match db.get_key(version, &req.identifier).await {
+        Ok(existing) => return imported_with(&state, req, existing, &token_id).await,
+        Err(err) if matches!(err.current_context(), errors::DatabaseError::NotFound) => {}
+        Err(err) => return Err(err).switch(),
+    }
+
+    let metadata = serde_json::to_string(&KeyMetadata {
+        import_token_id: Some(token_id.clone()),
+        ..Default::default()
+    })
+    .change_context(errors::ApplicationErrorResponse::InternalServerError(
+        "Failed to serialize the key metadata",
+    ))?;
+
+    let mut data_key = Key {
+        version,
+        identifier: req.identifier.clone(),
+        key,
+        source,
+        parent: None,
+    }
+    .encrypt(&state)
+    .await
+    .switch()
+    .map_err(|err| {
+        logger::error!(?err);
+        err
+    })?;
+    data_key.key_metadata = Some(metadata);
+
+    // A concurrent import with the same token may have inserted the version first
+    let data_key = db.get_or_insert_data_key(data_key).await.switch()?;
+    imported_with(&state, req, data_key, &token_id).await
+}
+
+/// Accepts a key stored under the version of the import token only if it was imported with the
+/// token and matches the key check value of the request
+async fn imported_with(
+    state: &TenantState,
+    req: ImportDataKeyRequest,
+    data_key: DataKey,
+    token_id: &str,
+) -> errors::CustomResult<DataKeyCreateResponse, errors::ApplicationErrorResponse> {
+    let metadata = data_key
+        .key_metadata
+        .as_deref()
+        .map(serde_json::from_str::<KeyMetadata>)
+        .transpose()
+        .change_context(errors::ApplicationErrorResponse::InternalServerError(
+            "Failed to parse the key metadata",
+        ))?
+        .unwrap_or_default();
+    error_stack::ensure!(
+        metadata.import_token_id.as_deref() == Some(token_id),
+        errors::ApplicationErrorResponse::UniqueViolation
+    );
+
+    let key_version = data_key.version;
+    let kcv = super::key_check_value(state, data_key).await?;
+    error_stack::ensure!(
+        kcv.eq_ignore_ascii_case(&req.kcv),
+        errors::ApplicationErrorResponse::UniqueViolation
+    );
+
+    Ok(DataKeyCreateResponse {
+        identifier: req.identifier,
+        key_version,
+        kcv,
+    })
+}
+
+/// Version after the newest one of the identifier, `v1` if the identifier has no keys yet.
+pub(super) async fn next_version(
+    state: &TenantState,
+    identifier: &Identifier,
+) -> errors::CustomResult<Version, errors::ApplicationErrorResponse> {
+    match state.get_db_pool().get_newest_version(identifier).await {
+        Ok(version) => version.increment().switch(),
+        Err(err) if matches!(err.current_context(), errors::DatabaseError::NotFound) => {
+            Ok(Version::default())
+        }
+        Err(err) => Err(err).switch(),
+    }
+}
@@ -0,0 +1,88 @@
+use base64::Engine;
+use error_stack::ResultExt;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+
+use crate::{
+    consts::base64::BASE64_ENGINE,
+    crypto::key_wrap,
+    env::observability as logger,
+    errors::{self, SwitchError},
+    multitenancy::TenantState,
+    types::{
+        Key,
+        key::Version,
+        requests::{KeyToWrap, UnwrapKeyRequest, WrapKeyRequest},
+        response::{UnwrapKeyResponse, WrapKeyResponse},
+    },
+};
+
+pub async fn wrap_key(
+    state: TenantState,
+    req: WrapKeyRequest,
+) -> errors::CustomResult<WrapKeyResponse, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let key = match req.key {
+        KeyToWrap::Plaintext { key } => BASE64_ENGINE
+            .decode(key.peek())
+            .map(StrongSecret::new)
+            .change_context(errors::ApplicationErrorResponse::ParsingFailed(
+                "Failed to decode the base64 key".to_string(),
+            ))?,
+        KeyToWrap::Import { unwrap_with } => unwrap(&state, unwrap_with).await?,
+    };
+
+    let version = Version::get_latest(&req.identifier, &state).await;
+    let kek = Key::get_key(&state, &req.identifier, version)
+        .await
+        .switch()?;
+
+    let wrapped_key = key_wrap::wrap(&kek.key, req.algorithm, key.peek())
+        .map_err(|err| {
+            logger::error!(key_wrap_error=?err);
+            err
+        })
+        .switch()?;
+
+    Ok(WrapKeyResponse {
+        identifier: req.identifier,
+        key_version: kek.version,
+        algorithm: req.algorithm,
+        wrapped_key: BASE64_ENGINE.encode(wrapped_key),
+    })
+}
+
+pub async fn unwrap_key(
+    state: TenantState,
+    req: UnwrapKeyRequest,
+) -> errors::CustomResult<UnwrapKeyResponse, errors::ApplicationErrorResponse> {
+    let key = unwrap(&state, req).await?;
+
+    Ok(UnwrapKeyResponse {
+        key: BASE64_ENGINE.encode(key.peek()).into(),
+    })
+}
+
+async fn unwrap(
+    state: &TenantState,
+    req: UnwrapKeyRequest,
+) -> errors::CustomResult<StrongSecret<Vec<u8>>, errors::ApplicationErrorResponse> {
+    state.validate_identifier(&req.identifier)?;
+
+    let wrapped_key = BASE64_ENGINE.decode(&req.wrapped_key).change_context(
+        errors::ApplicationErrorResponse::ParsingFailed(
+            "Failed to decode the base64 wrapped key".to_string(),
+        ),
+    )?;
+
+    let kek = Key::get_key(state, &req.identifier, req.key_version)
+        .await
+        .switch()?;
+
+    key_wrap::unwrap(&kek.key, req.algorithm, &wrapped_key)
+        .map_err(|err| {
+            logger::error!(key_unwrap_error=?err);
+            err
+        })
+        .switch()
+}
@@ -1,10 +1,19 @@
 pub(crate) mod aes256;
+pub(crate) mod hpke;
+pub(crate) mod jwe;
+pub mod kcv;
+pub mod key_wrap;
 pub(crate) mod kms;
+pub mod purpose;
+pub mod recipient;
+pub mod tr31;
 pub(crate) mod vault;
+pub mod wrapping_key;
 
 use std::{ops::Deref, sync::Arc};
 
 use hyperswitch_masking::StrongSecret;
+use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 
 use crate::{
@@ -13,7 +22,7 @@ use crate::{
     services::aws::AwsKmsClient,
 };
 
-#[derive(Clone, EnumString, Display)]
+#[derive(Clone, EnumString, Display, Deserialize, Serialize)]
 pub enum Source {
     KMS,
     AESLocal,
@@ -35,6 +44,8 @@ pub trait Crypto {
 
 #[async_trait::async_trait]
 pub trait KeyManagement {
+    /// Source recorded for keys created with this backend
+    fn source(&self) -> Source;
     async fn generate_key(
         &self,
     ) -> CustomResult<(Source, StrongSecret<[u8; 32]>), errors::CryptoError>;
@@ -46,10 +57,20 @@ pub trait KeyManagement {
         &self,
         input: StrongSecret<Vec<u8>>,
     ) -> CustomResult<StrongSecret<Vec<u8>>, errors::CryptoError>;
+    /// Decrypts the keys in order. Backends without a batch API decrypt them concurrently.
+    async fn decrypt_keys(
+        &self,
+        inputs: Vec<StrongSecret<Vec<u8>>>,
+    ) -> CustomResult<Vec<StrongSecret<Vec<u8>>>, errors::CryptoError> {
+        futures::future::try_join_all(inputs.into_iter().map(|input| self.decrypt_key(input))).await
+    }
 }
 
 #[async_trait::async_trait]
 impl KeyManagement for AwsKmsClient {
+    fn source(&self) -> Source {
+        Source::KMS
+    }
     async fn generate_key(
         &self,
     ) -> CustomResult<(Source, StrongSecret<[u8; 32]>), errors::CryptoError> {
@@ -70,6 +91,9 @@ impl KeyManagement for AwsKmsClient {
 }
 #[async_trait::async_trait]
 impl KeyManagement for GcmAes256 {
+    fn source(&self) -> Source {
+        Source::AESLocal
+    }
     async fn generate_key(
         &self,
     ) -> CustomResult<(Source, StrongSecret<[u8; 32]>), errors::CryptoError> {
@@ -87,10 +111,22 @@ impl KeyManagement for GcmAes256 {
     ) -> CustomResult<StrongSecret<Vec<u8>>, errors::CryptoError> {
         <Self as Crypto>::decrypt(self, input)
     }
+    async fn decrypt_keys(
+        &self,
+        inputs: Vec<StrongSecret<Vec<u8>>>,
+    ) -> CustomResult<Vec<StrongSecret<Vec<u8>>>, errors::CryptoError> {
+        inputs
+            .into_iter()
+            .map(|input| <Self as Crypto>::decrypt(self, input))
+            .collect()
+    }
 }
 
 #[async_trait::async_trait]
 impl KeyManagement for Vault {
+    fn source(&self) -> Source {
+        Source::HashicorpVault
+    }
     async fn generate_key(
         &self,
     ) -> CustomResult<(Source, StrongSecret<[u8; 32]>), errors::CryptoError> {
@@ -108,6 +144,12 @@ impl KeyManagement for Vault {
     ) -> CustomResult<StrongSecret<Vec<u8>>, errors::CryptoError> {
         <Self as Crypto>::decrypt(self, input).await
     }
+    async fn decrypt_keys(
+        &self,
+        inputs: Vec<StrongSecret<Vec<u8>>>,
+    ) -> CustomResult<Vec<StrongSecret<Vec<u8>>>, errors::CryptoError> {
+        self.decrypt_batch(inputs).await
+    }
 }
 
 pub type Backend = dyn KeyManagement + Send + Sync;
@@ -0,0 +1,183 @@
+use aws_lc_rs::{aead, agreement, error::Unspecified, hkdf, hmac};
+use error_stack::ResultExt;
+
+use crate::errors::{self, CustomResult};
+
+const KEM_ID: u16 = 0x0020;
+const KDF_ID: u16 = 0x0001;
+const AEAD_ID: u16 = 0x0002;
+const MODE_BASE: u8 = 0x00;
+const N_SECRET: usize = 32;
+const N_K: usize = 32;
+const N_N: usize = aead::NONCE_LEN;
+
+/// Length of a raw X25519 public key, which is also the length of the `enc` prefix.
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+struct Len(usize);
+
+impl hkdf::KeyType for Len {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// Single-shot HPKE (RFC 9180) base mode seal using `DHKEM(X25519, HKDF-SHA256)`, `HKDF-SHA256`
+/// and `AES-256-GCM`. Returns `enc || ciphertext` for the recipient's raw X25519 public key.
+pub fn seal(
+    recipient: &[u8],
+    info: &[u8],
+    plaintext: &[u8],
+) -> CustomResult<Vec<u8>, errors::CryptoError> {
+    let ephemeral = agreement::PrivateKey::generate(&agreement::X25519)
+        .change_context(errors::CryptoError::EncryptionFailed("HPKE"))?;
+
+    seal_with_ephemeral(&ephemeral, recipient, info, plaintext)
+        .change_context(errors::CryptoError::EncryptionFailed("HPKE"))
+}
+
+/// Opens `enc || ciphertext` produced by [`seal`] with the recipient's raw X25519 private key.
+pub fn open(
+    private_key: &[u8],
+    info: &[u8],
+    sealed: &[u8],
+) -> CustomResult<Vec<u8>, errors::CryptoError> {
+    open_raw(private_key, info, sealed)
+        .change_context(errors::CryptoError::DecryptionFailed("HPKE"))
+}
+
+fn kem_suite_id() -> Vec<u8> {
+    [b"KEM".as_slice(), &KEM_ID.to_be_bytes()].concat()
+}
+
+fn hpke_suite_id() -> Vec<u8> {
+    [
+        b"HPKE".as_slice(),
+        &KEM_ID.to_be_bytes(),
+        &KDF_ID.to_be_bytes(),
+        &AEAD_ID.to_be_bytes(),
+    ]
+    .concat()
+}
+
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, salt);
+    let labeled_ikm = [b"HPKE-v1".as_slice(), suite_id, label, ikm].concat();
+    hmac::sign(&key, &labeled_ikm).as_ref().to_vec()
+}
+
+fn labeled_expand(
+    suite_id: &[u8],
+    prk: &[u8],
+    label: &[u8],
+    info: &[u8],
+    len: usize,
+) -> Result<Vec<u8>, Unspecified> {
+    let length = u16::try_from(len).map_err(|_| Unspecified)?.to_be_bytes();
+    let labeled_info = [&length, b"HPKE-v1".as_slice(), suite_id, label, info].concat();
+    let mut out = vec![0_u8; len];
+    hkdf::Prk::new_less_safe(hkdf::HKDF_SHA256, prk)
+        .expand(&[&labeled_info], Len(len))?
+        .fill(&mut out)?;
+    Ok(out)
+}
+
+fn key_schedule(
+    dh: &[u8],
+    enc: &[u8],
+    recipient: &[u8],
+    info: &[u8],
+) -> Result<(aead::LessSafeKey, aead::Nonce), Unspecified> {
+    let kem_suite_id = kem_suite_id();
+    let kem_context = [enc, recipient].concat();
+    let eae_prk = labeled_extract(&kem_suite_id, b"", b"eae_prk", dh);
+    let shared_secret = labeled_expand(
+        &kem_suite_id,
+        &eae_prk,
+        b"shared_secret",
+        &kem_context,
+        N_SECRET,
+    )?;
+
+    let suite_id = hpke_suite_id();
+    let psk_id_hash = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+    let info_hash = labeled_extract(&suite_id, b"", b"info_hash", info);
+    let key_schedule_context = [&[MODE_BASE], psk_id_hash.as_slice(), &info_hash].concat();
+    let secret = labeled_extract(&suite_id, &shared_secret, b"secret", b"");
+    let key = labeled_expand(&suite_id, &secret, b"key", &key_schedule_context, N_K)?;
+    let base_nonce = labeled_expand(
+        &suite_id,
+        &secret,
+        b"base_nonce",
+        &key_schedule_context,
+        N_N,
+    )?;
+
+    Ok((
+        aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_256_GCM, &key)?),
+        aead::Nonce::try_assume_unique_for_key(&base_nonce)?,
+    ))
+}
+
+fn seal_with_ephemeral(
+    ephemeral: &agreement::PrivateKey,
+    recipient: &[u8],
+    info: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Unspecified> {
+    let enc = ephemeral.compute_public_key()?.as_ref().to_vec();
+    let peer = agreement::UnparsedPublicKey::new(&agreement::X25519, recipient);
+    let dh = agreement::agree(ephemeral, &peer, Unspecified, |secret| Ok(secret.to_vec()))?;
+
+    let (key, nonce) = key_schedule(&dh, &enc, recipient, info)?;
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut in_out)?;
+
+    Ok([enc, in_out].concat())
+}
+
+fn open_raw(private_key: &[u8], info: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Unspecified> {
+    let private_key = agreement::PrivateKey::from_private_key(&agreement::X25519, private_key)?;
+    let recipient = private_key.compute_public_key()?;
+    let (enc, ciphertext) = sealed.split_at_checked(PUBLIC_KEY_LEN).ok_or(Unspecified)?;
+    let peer = agreement::UnparsedPublicKey::new(&agreement::X25519, enc);
+    let dh = agreement::agree(&private_key, &peer, Unspecified, |secret| {
+        Ok(secret.to_vec())
+    })?;
+
+    let (key, nonce) = key_schedule(&dh, enc, recipient.as_ref(), info)?;
+    let mut in_out = ciphertext.to_vec();
+    let len = key
+        .open_in_place(nonce, aead::Aad::empty(), &mut in_out)?
+        .len();
+    in_out.truncate(len);
+    Ok(in_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_seal_known_answer() {
+        let recipient =
+            hex::decode("b98d41a4170a419d643ffd24f8c175bb35b32f61cd47c7ac9fdfd0617aa4f02a")
+                .unwrap();
+        let ephemeral = agreement::PrivateKey::from_private_key(
+            &agreement::X25519,
+            &hex::decode("1d7a6f0e35c2b4a8917f5e3d2c1b0a99887766554433221100ffeeddccbbaa98")
+                .unwrap(),
+        )
+        .unwrap();
+
+        let sealed =
+            seal_with_ephemeral(&ephemeral, &recipient, b"cripta", b"4111111111111111").unwrap();
+
+        assert_eq!(
+            hex::encode(sealed),
+            "f5d3e3decb00106458ce9ed668fe692330ed785f726ae418085e8022c03f1610\
+             6feb5d28879aa9b7f771ffc7bb38262c542a37e491ead404f0040ff9cca2d505"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use aws_lc_rs::{
+    aead,
+    error::Unspecified,
+    rand::{self, SecureRandom},
+    rsa::{OAEP_SHA256_MGF1SHA256, OaepPublicEncryptingKey, PublicEncryptingKey},
+};
+use base64::Engine;
+use error_stack::ResultExt;
+
+use crate::{
+    consts::base64::BASE64_URL_SAFE_ENGINE,
+    errors::{self, CustomResult},
+};
+
+/// Encrypts `plaintext` into a JWE compact serialization using `RSA-OAEP-256` for the content
+/// encryption key and `A256GCM` for the content. `public_key` is a DER encoded
+/// `SubjectPublicKeyInfo`.
+pub fn encrypt(
+    public_key: &[u8],
+    key_id: Option<&str>,
+    plaintext: &[u8],
+) -> CustomResult<String, errors::CryptoError> {
+    encrypt_compact(public_key, key_id, plaintext)
+        .change_context(errors::CryptoError::EncryptionFailed("JWE"))
+}
+
+/// Checks whether the provided DER bytes are a usable RSA public key for `RSA-OAEP-256`.
+pub fn validate_public_key(public_key: &[u8]) -> bool {
+    PublicEncryptingKey::from_der(public_key)
+        .ok()
+        .and_then(|key| OaepPublicEncryptingKey::new(key).ok())
+        .is_some()
+}
+
+fn encrypt_compact(
+    public_key: &[u8],
+    key_id: Option<&str>,
+    plaintext: &[u8],
+) -> Result<String, Unspecified> {
+    let rng = rand::SystemRandom::new();
+
+    let mut header = serde_json::json!({ "alg": "RSA-OAEP-256", "enc": "A256GCM" });
+    if let Some(kid) = key_id {
+        header["kid"] = serde_json::Value::from(kid);
+    }
+    let protected =
+        BASE64_URL_SAFE_ENGINE.encode(serde_json::to_vec(&header).map_err(|_| Unspecified)?);
+
+    let mut cek = [0_u8; 32];
+    rng.fill(&mut cek)?;
+    let mut iv = [0_u8; aead::NONCE_LEN];
+    rng.fill(&mut iv)?;
+
+    let public_key = OaepPublicEncryptingKey::new(
+        PublicEncryptingKey::from_der(public_key).map_err(|_| Unspecified)?,
+    )?;
+    let mut encrypted_key = vec![0_u8; public_key.ciphertext_size()];
+    let encrypted_key =
+        public_key.encrypt(&OAEP_SHA256_MGF1SHA256, &cek, &mut encrypted_key, None)?;
+
+    let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_256_GCM, &cek)?);
+    let mut ciphertext = plaintext.to_vec();
+    let tag = key.seal_in_place_separate_tag(
+        aead::Nonce::assume_unique_for_key(iv),
+        aead::Aad::from(protected.as_bytes()),
+        &mut ciphertext,
+    )?;
+
+    Ok([
+        protected,
+        BASE64_URL_SAFE_ENGINE.encode(encrypted_key),
+        BASE64_URL_SAFE_ENGINE.encode(iv),
+        BASE64_URL_SAFE_ENGINE.encode(ciphertext),
+        BASE64_URL_SAFE_ENGINE.encode(tag.as_ref()),
+    ]
+    .join("."))
+}
@@ -0,0 +1,35 @@
+use aws_lc_rs::cmac;
+use error_stack::ResultExt;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+
+use crate::errors::{self, CustomResult};
+
+const KCV_LEN: usize = 3;
+
+/// Key check value of an AES-256 key, the first three bytes of the AES-CMAC of a zero block as
+/// uppercase hex.
+pub fn compute(key: &StrongSecret<[u8; 32]>) -> CustomResult<String, errors::CryptoError> {
+    cmac::Key::new(cmac::AES_256, key.peek())
+        .and_then(|key| cmac::sign(&key, &[0_u8; 16]))
+        .map(|tag| hex::encode_upper(&tag.as_ref()[..KCV_LEN]))
+        .change_context(errors::CryptoError::InvalidKey)
+}
+
+pub fn verify(key: &StrongSecret<[u8; 32]>, kcv: &str) -> CustomResult<bool, errors::CryptoError> {
+    Ok(compute(key)?.eq_ignore_ascii_case(kcv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_kcv() {
+        let key = <[u8; 32]>::try_from((0_u8..32).collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(compute(&key.into()).unwrap(), "377822");
+        assert!(verify(&key.into(), "377822").unwrap());
+        assert!(!verify(&key.into(), "000000").unwrap());
+    }
+}
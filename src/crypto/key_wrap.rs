@@ -0,0 +1,99 @@
+use aws_lc_rs::{
+    error::Unspecified,
+    key_wrap::{AES_256, AesKek, KeyWrap, KeyWrapPadded},
+};
+use error_stack::ResultExt;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{self, CustomResult};
+
+const SEMIBLOCK_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum KeyWrapAlgorithm {
+    /// AES Key Wrap with Padding (RFC 5649)
+    #[default]
+    #[serde(rename = "AES-KWP")]
+    AesKwp,
+    /// AES Key Wrap (RFC 3394), the key length must be a multiple of 8 bytes
+    #[serde(rename = "AES-KW")]
+    AesKw,
+}
+
+pub fn wrap(
+    kek: &StrongSecret<[u8; 32]>,
+    algorithm: KeyWrapAlgorithm,
+    key: &[u8],
+) -> CustomResult<Vec<u8>, errors::CryptoError> {
+    wrap_raw(kek.peek(), algorithm, key)
+        .change_context(errors::CryptoError::EncryptionFailed("AES-KW"))
+}
+
+pub fn unwrap(
+    kek: &StrongSecret<[u8; 32]>,
+    algorithm: KeyWrapAlgorithm,
+    wrapped_key: &[u8],
+) -> CustomResult<StrongSecret<Vec<u8>>, errors::CryptoError> {
+    unwrap_raw(kek.peek(), algorithm, wrapped_key)
+        .map(StrongSecret::new)
+        .change_context(errors::CryptoError::DecryptionFailed("AES-KW"))
+}
+
+fn wrap_raw(kek: &[u8], algorithm: KeyWrapAlgorithm, key: &[u8]) -> Result<Vec<u8>, Unspecified> {
+    let kek = AesKek::new(&AES_256, kek)?;
+    let mut output = vec![0_u8; key.len() + 2 * SEMIBLOCK_LEN];
+    let len = match algorithm {
+        KeyWrapAlgorithm::AesKwp => kek.wrap_with_padding(key, &mut output)?.len(),
+        KeyWrapAlgorithm::AesKw => kek.wrap(key, &mut output)?.len(),
+    };
+    output.truncate(len);
+    Ok(output)
+}
+
+fn unwrap_raw(
+    kek: &[u8],
+    algorithm: KeyWrapAlgorithm,
+    wrapped_key: &[u8],
+) -> Result<Vec<u8>, Unspecified> {
+    let kek = AesKek::new(&AES_256, kek)?;
+    let mut output = vec![0_u8; wrapped_key.len()];
+    let len = match algorithm {
+        KeyWrapAlgorithm::AesKwp => kek.unwrap_with_padding(wrapped_key, &mut output)?.len(),
+        KeyWrapAlgorithm::AesKw => kek.unwrap(wrapped_key, &mut output)?.len(),
+    };
+    output.truncate(len);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_rfc3394_wrap_256_bit_key_with_256_bit_kek() {
+        let kek = <[u8; 32]>::try_from((0_u8..32).collect::<Vec<_>>()).unwrap();
+        let key = hex::decode("00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f")
+            .unwrap();
+
+        let wrapped = wrap(&kek.into(), KeyWrapAlgorithm::AesKw, &key).unwrap();
+        assert_eq!(
+            hex::encode(&wrapped),
+            "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21"
+        );
+
+        let unwrapped = unwrap(&kek.into(), KeyWrapAlgorithm::AesKw, &wrapped).unwrap();
+        assert_eq!(unwrapped.peek(), &key);
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_kwp_rejects_tampered_key() {
+        let kek = StrongSecret::new([7_u8; 32]);
+        let mut wrapped = wrap(&kek, KeyWrapAlgorithm::AesKwp, b"working key").unwrap();
+        wrapped[0] ^= 1;
+
+        assert!(unwrap(&kek, KeyWrapAlgorithm::AesKwp, &wrapped).is_err());
+    }
+}
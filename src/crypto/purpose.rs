@@ -0,0 +1,56 @@
+use aws_lc_rs::hkdf;
+use error_stack::ResultExt;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+
+use crate::errors::{self, CustomResult};
+
+/// HKDF salt of purpose sub-keys, separating them from any other derivation of the data key
+const SALT: &[u8] = b"cripta-purpose-subkey";
+
+const MAX_PURPOSE_LEN: usize = 32;
+
+/// A purpose is recorded in the ciphertext, so it is limited to a short `[A-Za-z0-9_-]` label.
+pub fn is_valid(purpose: &str) -> bool {
+    !purpose.is_empty()
+        && purpose.len() <= MAX_PURPOSE_LEN
+        && purpose
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-'))
+}
+
+/// Derives the sub-key of `purpose` from a data key with HKDF-SHA256, using the purpose as info.
+pub fn derive_key(
+    key: &StrongSecret<[u8; 32]>,
+    purpose: &str,
+) -> CustomResult<StrongSecret<[u8; 32]>, errors::CryptoError> {
+    let mut sub_key = [0_u8; 32];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, SALT)
+        .extract(key.peek())
+        .expand(&[purpose.as_bytes()], hkdf::HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut sub_key))
+        .change_context(errors::CryptoError::InvalidKey)?;
+
+    Ok(sub_key.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_derive_purpose_key() {
+        let key = StrongSecret::new([0x11_u8; 32]);
+
+        let card = derive_key(&key, "card").unwrap();
+        assert_eq!(
+            hex::encode(card.peek()),
+            "be1193e6878c23dd85653625c6b1ec601b7661af0eca1e5524bd3beffdd266a8"
+        );
+        assert_ne!(card.peek(), derive_key(&key, "pii").unwrap().peek());
+
+        assert!(is_valid("bank_account"));
+        assert!(!is_valid("card:data"));
+        assert!(!is_valid(""));
+    }
+}
@@ -0,0 +1,91 @@
+use base64::Engine;
+use error_stack::IntoReport;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::base64::BASE64_ENGINE,
+    crypto::{hpke, jwe},
+    errors::{self, CustomResult},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, strum::Display)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum RecipientAlgorithm {
+    /// RFC 9180 HPKE with a raw X25519 public key
+    Hpke,
+    /// JWE compact serialization with `RSA-OAEP-256` and `A256GCM`
+    Jwe,
+}
+
+/// A third party which is allowed to receive data re-encrypted to its public key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recipient {
+    pub algorithm: RecipientAlgorithm,
+    /// Base64 encoded public key. Raw 32 bytes for HPKE and DER `SubjectPublicKeyInfo` for JWE.
+    pub public_key: String,
+    /// Sent as `kid` in the JWE header
+    pub key_id: Option<String>,
+    /// HPKE application info bound to the ciphertext
+    #[serde(default)]
+    pub info: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Recipients(pub FxHashMap<String, Recipient>);
+
+impl Recipients {
+    pub fn get(&self, name: &str) -> Option<&Recipient> {
+        self.0.get(name)
+    }
+
+    pub(crate) fn validate(&self) -> CustomResult<(), errors::ParsingError> {
+        self.0.iter().try_for_each(|(name, recipient)| {
+            error_stack::ensure!(
+                recipient.is_valid(),
+                errors::ParsingError::DecodingFailed(format!(
+                    "Invalid public key configured for the recipient {name}"
+                ))
+            );
+            Ok(())
+        })
+    }
+}
+
+impl Recipient {
+    fn public_key(&self) -> CustomResult<Vec<u8>, errors::CryptoError> {
+        BASE64_ENGINE
+            .decode(&self.public_key)
+            .map_err(|_| errors::CryptoError::InvalidKey.into_report())
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        self.public_key()
+            .map(|key| match self.algorithm {
+                RecipientAlgorithm::Hpke => key.len() == hpke::PUBLIC_KEY_LEN,
+                RecipientAlgorithm::Jwe => jwe::validate_public_key(&key),
+            })
+            .unwrap_or(false)
+    }
+
+    /// Encrypts the plaintext to the recipient. HPKE output is base64 encoded `enc || ciphertext`
+    /// and JWE output is the compact serialization.
+    pub fn seal(
+        &self,
+        plaintext: StrongSecret<Vec<u8>>,
+    ) -> CustomResult<String, errors::CryptoError> {
+        let public_key = self.public_key()?;
+
+        match self.algorithm {
+            RecipientAlgorithm::Hpke => {
+                hpke::seal(&public_key, self.info.as_bytes(), plaintext.peek())
+                    .map(|sealed| BASE64_ENGINE.encode(sealed))
+            }
+            RecipientAlgorithm::Jwe => {
+                jwe::encrypt(&public_key, self.key_id.as_deref(), plaintext.peek())
+            }
+        }
+    }
+}
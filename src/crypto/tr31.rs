@@ -0,0 +1,356 @@
+use aws_lc_rs::{
+    cipher::{
+        self, DecryptingKey, DecryptionContext, EncryptingKey, EncryptionContext, UnboundCipherKey,
+    },
+    cmac,
+    error::Unspecified,
+    iv::FixedLength,
+    rand::{self, SecureRandom},
+};
+use error_stack::ResultExt;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{self, CustomResult};
+
+const VERSION_ID: u8 = b'D';
+const HEADER_LEN: usize = 16;
+const BLOCK_LEN: usize = 16;
+const MAX_BLOCK_LEN: usize = 9999;
+const ENCRYPTION_KEY_USAGE: u16 = 0x0000;
+const MAC_KEY_USAGE: u16 = 0x0001;
+const AES_256_ALGORITHM: u16 = 0x0004;
+const AES_256_BITS: u16 = 0x0100;
+
+/// Header attributes of a TR-31 key block which are kept as key metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KeyBlockAttributes {
+    /// e.g. `D0` for symmetric data encryption or `P0` for PIN encryption
+    pub key_usage: String,
+    /// e.g. `A` for AES or `T` for TDES
+    pub algorithm: char,
+    /// e.g. `B` for encrypt and decrypt or `E` for encrypt only
+    pub mode_of_use: char,
+    pub key_version_number: String,
+    /// `E` exportable, `N` non-exportable or `S` sensitive
+    pub exportability: char,
+}
+
+impl Default for KeyBlockAttributes {
+    /// AES data encryption key usable for both encryption and decryption
+    fn default() -> Self {
+        Self {
+            key_usage: "D0".to_string(),
+            algorithm: 'A',
+            mode_of_use: 'B',
+            key_version_number: "00".to_string(),
+            exportability: 'E',
+        }
+    }
+}
+
+impl KeyBlockAttributes {
+    fn is_valid(&self) -> bool {
+        let is_field = |c: char| c.is_ascii_digit() || c.is_ascii_uppercase();
+
+        self.key_usage.len() == 2
+            && self.key_usage.chars().all(is_field)
+            && self.key_version_number.len() == 2
+            && self.key_version_number.chars().all(is_field)
+            && [self.algorithm, self.mode_of_use, self.exportability]
+                .into_iter()
+                .all(is_field)
+    }
+
+    /// `N` marks a key which must not be exported under any key block protection key
+    pub fn is_exportable(&self) -> bool {
+        self.exportability != 'N'
+    }
+
+    /// AES data encryption key usable for both encryption and decryption, the only kind of key a
+    /// data key is used as
+    pub fn is_data_key(&self) -> bool {
+        self.key_usage == "D0" && self.algorithm == 'A' && matches!(self.mode_of_use, 'B' | 'N')
+    }
+
+    /// Whether `requested` describes the same kind of key with a mode of use and exportability
+    /// that are at most as permissive as these attributes
+    pub fn permits(&self, requested: &Self) -> bool {
+        let mode_of_use = requested.mode_of_use == self.mode_of_use
+            || (matches!(self.mode_of_use, 'B' | 'N')
+                && matches!(requested.mode_of_use, 'B' | 'D' | 'E'));
+        let exportability =
+            requested.exportability == self.exportability || requested.exportability == 'N';
+
+        requested.key_usage == self.key_usage
+            && requested.algorithm == self.algorithm
+            && mode_of_use
+            && exportability
+    }
+}
+
+/// Unwraps a TR-31 version `D` key block protected by an AES-256 key block protection key.
+pub fn import(
+    kbpk: &StrongSecret<[u8; 32]>,
+    key_block: &str,
+) -> CustomResult<(KeyBlockAttributes, StrongSecret<Vec<u8>>), errors::CryptoError> {
+    unwrap_raw(kbpk.peek(), key_block)
+        .map(|(attributes, key)| (attributes, StrongSecret::new(key)))
+        .change_context(errors::CryptoError::DecryptionFailed("TR-31"))
+}
+
+/// Wraps the key into a TR-31 version `D` key block without optional blocks.
+pub fn export(
+    kbpk: &StrongSecret<[u8; 32]>,
+    attributes: &KeyBlockAttributes,
+    key: &[u8],
+) -> CustomResult<String, errors::CryptoError> {
+    wrap_raw(kbpk.peek(), attributes, key)
+        .change_context(errors::CryptoError::EncryptionFailed("TR-31"))
+}
+
+fn derive_key(kbpk: &cmac::Key, key_usage: u16) -> Result<[u8; 32], Unspecified> {
+    let mut derived = [0_u8; 32];
+    for (counter, chunk) in (1_u8..).zip(derived.chunks_mut(BLOCK_LEN)) {
+        let input = [
+            [counter].as_slice(),
+            &key_usage.to_be_bytes(),
+            &[0x00],
+            &AES_256_ALGORITHM.to_be_bytes(),
+            &AES_256_BITS.to_be_bytes(),
+        ]
+        .concat();
+        chunk.copy_from_slice(cmac::sign(kbpk, &input)?.as_ref());
+    }
+    Ok(derived)
+}
+
+fn derive_keys(kbpk: &[u8]) -> Result<([u8; 32], [u8; 32]), Unspecified> {
+    let kbpk = cmac::Key::new(cmac::AES_256, kbpk)?;
+    Ok((
+        derive_key(&kbpk, ENCRYPTION_KEY_USAGE)?,
+        derive_key(&kbpk, MAC_KEY_USAGE)?,
+    ))
+}
+
+fn parse_hex_len(field: &str) -> Result<usize, Unspecified> {
+    usize::from_str_radix(field, 16).map_err(|_| Unspecified)
+}
+
+/// Parses the fixed header and skips the optional blocks, returning the attributes and the
+/// length of the full header.
+fn parse_header(key_block: &str) -> Result<(KeyBlockAttributes, usize), Unspecified> {
+    let field = |start: usize, end: usize| key_block.get(start..end).ok_or(Unspecified);
+
+    if key_block.len() < HEADER_LEN
+        || !key_block.is_ascii()
+        || key_block.as_bytes().first() != Some(&VERSION_ID)
+        || field(1, 5)?.parse::<usize>().map_err(|_| Unspecified)? != key_block.len()
+    {
+        return Err(Unspecified);
+    }
+
+    let attributes = KeyBlockAttributes {
+        key_usage: field(5, 7)?.to_string(),
+        algorithm: char::from(key_block.as_bytes()[7]),
+        mode_of_use: char::from(key_block.as_bytes()[8]),
+        key_version_number: field(9, 11)?.to_string(),
+        exportability: char::from(key_block.as_bytes()[11]),
+    };
+    if !attributes.is_valid() {
+        return Err(Unspecified);
+    }
+
+    let optional_blocks = field(12, 14)?.parse::<usize>().map_err(|_| Unspecified)?;
+    let mut header_len = HEADER_LEN;
+    for _ in 0..optional_blocks {
+        let block_len = match parse_hex_len(field(header_len + 2, header_len + 4)?)? {
+            0 => {
+                let len_of_len = parse_hex_len(field(header_len + 4, header_len + 6)?)?;
+                parse_hex_len(field(header_len + 6, header_len + 6 + len_of_len)?)?
+            }
+            len => len,
+        };
+        header_len = header_len.checked_add(block_len).ok_or(Unspecified)?;
+        field(0, header_len)?;
+    }
+
+    Ok((attributes, header_len))
+}
+
+fn unwrap_raw(kbpk: &[u8], key_block: &str) -> Result<(KeyBlockAttributes, Vec<u8>), Unspecified> {
+    let (attributes, header_len) = parse_header(key_block)?;
+    let (header, body) = key_block.split_at(header_len);
+    let (encrypted, mac) = body
+        .split_at_checked(body.len().checked_sub(2 * BLOCK_LEN).ok_or(Unspecified)?)
+        .ok_or(Unspecified)?;
+
+    let mut key_data = hex::decode(encrypted).map_err(|_| Unspecified)?;
+    let mac = <[u8; BLOCK_LEN]>::try_from(hex::decode(mac).map_err(|_| Unspecified)?)
+        .map_err(|_| Unspecified)?;
+    if key_data.is_empty() || key_data.len() % BLOCK_LEN != 0 {
+        return Err(Unspecified);
+    }
+
+    let (kbek, kbak) = derive_keys(kbpk)?;
+    DecryptingKey::cbc(UnboundCipherKey::new(&cipher::AES_256, &kbek)?)?.decrypt(
+        &mut key_data,
+        DecryptionContext::Iv128(FixedLength::from(mac)),
+    )?;
+    cmac::verify(
+        &cmac::Key::new(cmac::AES_256, &kbak)?,
+        &[header.as_bytes(), &key_data].concat(),
+        &mac,
+    )?;
+
+    let key_bits = u16::from_be_bytes([key_data[0], key_data[1]]);
+    let key_len = usize::from(key_bits / 8);
+    if key_bits % 8 != 0 || key_len == 0 || key_len > key_data.len() - 2 {
+        return Err(Unspecified);
+    }
+
+    Ok((attributes, key_data[2..2 + key_len].to_vec()))
+}
+
+fn wrap_raw(
+    kbpk: &[u8],
+    attributes: &KeyBlockAttributes,
+    key: &[u8],
+) -> Result<String, Unspecified> {
+    if !attributes.is_valid() || key.is_empty() {
+        return Err(Unspecified);
+    }
+
+    let key_bits = u16::try_from(key.len() * 8).map_err(|_| Unspecified)?;
+    let padded_len = (2 + key.len()).div_ceil(BLOCK_LEN) * BLOCK_LEN;
+    let mut key_data = vec![0_u8; padded_len];
+    key_data[..2].copy_from_slice(&key_bits.to_be_bytes());
+    key_data[2..2 + key.len()].copy_from_slice(key);
+    rand::SystemRandom::new().fill(&mut key_data[2 + key.len()..])?;
+
+    let block_len = HEADER_LEN + 2 * padded_len + 2 * BLOCK_LEN;
+    if block_len > MAX_BLOCK_LEN {
+        return Err(Unspecified);
+    }
+    let header = format!(
+        "{}{block_len:04}{}{}{}{}{}0000",
+        char::from(VERSION_ID),
+        attributes.key_usage,
+        attributes.algorithm,
+        attributes.mode_of_use,
+        attributes.key_version_number,
+        attributes.exportability,
+    );
+
+    let (kbek, kbak) = derive_keys(kbpk)?;
+    let mac = cmac::sign(
+        &cmac::Key::new(cmac::AES_256, &kbak)?,
+        &[header.as_bytes(), &key_data].concat(),
+    )?;
+    let iv = <[u8; BLOCK_LEN]>::try_from(mac.as_ref()).map_err(|_| Unspecified)?;
+    EncryptingKey::cbc(UnboundCipherKey::new(&cipher::AES_256, &kbek)?)?.less_safe_encrypt(
+        &mut key_data,
+        EncryptionContext::Iv128(FixedLength::from(iv)),
+    )?;
+
+    Ok([header, hex::encode_upper(key_data), hex::encode_upper(iv)].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KBPK: &str = "88E1AB2A2E3DD38C1FA039A536500CC8A87AB9D62DC92C01058FA79F44657DE6";
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_import_key_block() {
+        let kbpk = <[u8; 32]>::try_from(hex::decode(KBPK).unwrap()).unwrap();
+        let key_block = "D0112P0AE00E0000B82679114F470F540165EDFBF7E250FCEA43F810D215F8D2\
+                         07E2E417C07156A27E8E31DA05F7425509593D03A457DC34";
+
+        // Same key with a key set identifier and a padding optional block in the header
+        let with_optional_blocks = "D0144P0AE00E0200KS1200604B120F9292PB0E0000000000\
+                                    4E1C6B8FCED4439B8FB1EDD992C260322BA504B7CFC057B5F4BB4EE4DC72A641\
+                                    1C27ED50B93F93E98F924015ADCA2820";
+
+        let (attributes, key) = import(&kbpk.into(), key_block).unwrap();
+        let (_, same_key) = import(&kbpk.into(), with_optional_blocks).unwrap();
+
+        assert_eq!(
+            hex::encode_upper(key.peek()),
+            "3F419E1CB7079442AA37474C2EFBF8B8"
+        );
+        assert_eq!(key.peek(), same_key.peek());
+        assert_eq!(
+            attributes,
+            KeyBlockAttributes {
+                key_usage: "P0".to_string(),
+                algorithm: 'A',
+                mode_of_use: 'E',
+                key_version_number: "00".to_string(),
+                exportability: 'E',
+            }
+        );
+    }
+
+    #[test]
+    fn test_requested_attributes_can_not_widen() {
+        let data_key = KeyBlockAttributes::default();
+        assert!(data_key.is_data_key());
+
+        let encrypt_only = KeyBlockAttributes {
+            mode_of_use: 'E',
+            ..Default::default()
+        };
+        let non_exportable = KeyBlockAttributes {
+            exportability: 'N',
+            ..Default::default()
+        };
+        let pin_key = KeyBlockAttributes {
+            key_usage: "P0".to_string(),
+            ..Default::default()
+        };
+        let sensitive = KeyBlockAttributes {
+            exportability: 'S',
+            ..Default::default()
+        };
+
+        assert!(!encrypt_only.is_data_key());
+        assert!(!pin_key.is_data_key());
+        assert!(data_key.permits(&encrypt_only));
+        assert!(data_key.permits(&non_exportable));
+        assert!(!data_key.permits(&pin_key));
+        assert!(!data_key.permits(&sensitive));
+        assert!(!encrypt_only.permits(&data_key));
+        assert!(!non_exportable.permits(&data_key));
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_export_round_trip_and_tamper() {
+        let kbpk = StrongSecret::new(<[u8; 32]>::try_from(hex::decode(KBPK).unwrap()).unwrap());
+        let attributes = KeyBlockAttributes {
+            key_usage: "D0".to_string(),
+            algorithm: 'A',
+            mode_of_use: 'B',
+            key_version_number: "00".to_string(),
+            exportability: 'N',
+        };
+
+        let key_block = export(&kbpk, &attributes, &[0x11; 32]).unwrap();
+        let (imported, key) = import(&kbpk, &key_block).unwrap();
+        assert_eq!(imported, attributes);
+        assert_eq!(key.peek(), &[0x11; 32]);
+        assert!(!imported.is_exportable());
+
+        let mut tampered = key_block.clone();
+        let flipped = if key_block.as_bytes()[20] == b'0' {
+            "1"
+        } else {
+            "0"
+        };
+        tampered.replace_range(20..21, flipped);
+        assert!(import(&kbpk, &tampered).is_err());
+    }
+}
@@ -4,7 +4,8 @@ use base64::Engine;
 use error_stack::{IntoReport, ResultExt};
 use futures::Future;
 use hyperswitch_masking::{PeekInterface, StrongSecret};
-use serde::Deserialize;
+use rustify_derive::Endpoint;
+use serde::{Deserialize, Serialize};
 use vaultrs::{
     api,
     client::{VaultClient, VaultClientSettingsBuilder},
@@ -47,6 +48,83 @@ impl Vault {
             settings,
         }
     }
+
+    /// Decrypts the ciphertexts in order with a single transit request
+    pub async fn decrypt_batch(
+        &self,
+        inputs: Vec<StrongSecret<Vec<u8>>>,
+    ) -> CustomResult<Vec<StrongSecret<Vec<u8>>>, errors::CryptoError> {
+        let batch_input = inputs
+            .iter()
+            .map(|input| {
+                String::from_utf8(input.peek().to_vec())
+                    .map(|ciphertext| BatchDecryptInput { ciphertext })
+                    .change_context(CryptoError::DecryptionFailed("Vault"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let response = api::exec_with_result(
+            &self.inner_client,
+            BatchDecryptRequest {
+                mount: self.settings.mount_point.clone(),
+                name: self.settings.encryption_key.clone(),
+                batch_input,
+            },
+        )
+        .await
+        .change_context(CryptoError::DecryptionFailed("HashiCorp Vault"))?;
+        error_stack::ensure!(
+            response.batch_results.len() == inputs.len(),
+            CryptoError::DecryptionFailed("HashiCorp Vault")
+        );
+
+        response
+            .batch_results
+            .into_iter()
+            .map(|result| {
+                let plaintext = result.plaintext.ok_or_else(|| {
+                    logger::error!(vault_batch_error = ?result.error);
+                    CryptoError::DecryptionFailed("HashiCorp Vault").into_report()
+                })?;
+                Ok(BASE64_ENGINE
+                    .decode(plaintext)
+                    .change_context(CryptoError::DecryptionFailed("HashiCorp Vault"))?
+                    .into())
+            })
+            .collect()
+    }
+}
+
+/// Transit decryption of several ciphertexts in one request, which vaultrs does not expose
+#[derive(Debug, Endpoint)]
+#[endpoint(
+    path = "{self.mount}/decrypt/{self.name}",
+    method = "POST",
+    response = "BatchDecryptResponse"
+)]
+struct BatchDecryptRequest {
+    #[endpoint(skip)]
+    mount: String,
+    #[endpoint(skip)]
+    name: String,
+    batch_input: Vec<BatchDecryptInput>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchDecryptInput {
+    ciphertext: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchDecryptResponse {
+    batch_results: Vec<BatchDecryptResult>,
+}
+
+/// Vault reports a failed item in its `error` instead of failing the whole batch
+#[derive(Debug, Deserialize)]
+struct BatchDecryptResult {
+    plaintext: Option<String>,
+    error: Option<String>,
 }
 
 #[async_trait::async_trait]
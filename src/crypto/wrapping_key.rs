@@ -0,0 +1,211 @@
+use aws_lc_rs::{
+    agreement,
+    encoding::{AsBigEndian, AsDer, Curve25519SeedBin, Pkcs8V1Der, PublicKeyX509Der},
+    error::Unspecified,
+    rand,
+    rsa::{KeySize, OAEP_SHA256_MGF1SHA256, OaepPrivateDecryptingKey, PrivateDecryptingKey},
+};
+use error_stack::ResultExt;
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::hpke,
+    errors::{self, CustomResult},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WrappingAlgorithm {
+    /// RSA 3072 public key as DER `SubjectPublicKeyInfo`, the key is encrypted with RSA-OAEP
+    /// using SHA-256 for both the hash and MGF1
+    #[default]
+    #[serde(rename = "RSA-OAEP-256")]
+    RsaOaep256,
+    /// Raw X25519 public key, the key is sealed with RFC 9180 HPKE base mode
+    /// (`DHKEM(X25519, HKDF-SHA256)`, `HKDF-SHA256`, `AES-256-GCM`) and an empty info
+    #[serde(rename = "HPKE")]
+    Hpke,
+}
+
+pub struct WrappingKeyPair {
+    pub public_key: Vec<u8>,
+    pub private_key: StrongSecret<Vec<u8>>,
+}
+
+/// Plaintext of the import token. The token is handed to the caller encrypted by the key manager
+/// so that the ephemeral private key never has to be kept by the service.
+pub struct ImportToken {
+    /// Random identifier, recorded with the imported key so that a token imports at most one key
+    pub id: [u8; 16],
+    pub algorithm: WrappingAlgorithm,
+    pub expires_at: i64,
+    /// Identifier and version of the data key the token is issued for
+    pub identifier: String,
+    pub key_version: i32,
+    pub private_key: StrongSecret<Vec<u8>>,
+}
+
+impl WrappingAlgorithm {
+    fn id(self) -> u8 {
+        match self {
+            Self::RsaOaep256 => 1,
+            Self::Hpke => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::RsaOaep256),
+            2 => Some(Self::Hpke),
+            _ => None,
+        }
+    }
+
+    /// Generates an ephemeral wrapping key pair
+    pub fn generate(self) -> CustomResult<WrappingKeyPair, errors::CryptoError> {
+        match self {
+            Self::RsaOaep256 => generate_rsa(),
+            Self::Hpke => generate_x25519(),
+        }
+        .change_context(errors::CryptoError::KeyGeneration)
+    }
+
+    pub fn unwrap(
+        self,
+        private_key: &StrongSecret<Vec<u8>>,
+        wrapped_key: &[u8],
+    ) -> CustomResult<StrongSecret<Vec<u8>>, errors::CryptoError> {
+        match self {
+            Self::RsaOaep256 => unwrap_rsa(private_key.peek(), wrapped_key)
+                .change_context(errors::CryptoError::DecryptionFailed("RSA-OAEP")),
+            Self::Hpke => hpke::open(private_key.peek(), b"", wrapped_key),
+        }
+        .map(StrongSecret::new)
+    }
+}
+
+impl ImportToken {
+    /// Random token identifier
+    pub fn generate_id() -> CustomResult<[u8; 16], errors::CryptoError> {
+        let mut id = [0_u8; 16];
+        rand::fill(&mut id).change_context(errors::CryptoError::KeyGeneration)?;
+        Ok(id)
+    }
+
+    pub fn to_bytes(&self) -> CustomResult<StrongSecret<Vec<u8>>, errors::CryptoError> {
+        let identifier_len = u16::try_from(self.identifier.len())
+            .change_context(errors::CryptoError::InvalidValue)?;
+
+        Ok(StrongSecret::new(
+            [
+                [self.algorithm.id()].as_slice(),
+                &self.id,
+                &self.expires_at.to_be_bytes(),
+                &self.key_version.to_be_bytes(),
+                &identifier_len.to_be_bytes(),
+                self.identifier.as_bytes(),
+                self.private_key.peek(),
+            ]
+            .concat(),
+        ))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> CustomResult<Self, errors::CryptoError> {
+        let (algorithm, rest) = bytes
+            .split_first()
+            .ok_or(errors::CryptoError::InvalidValue)?;
+        let (id, rest) = rest
+            .split_first_chunk::<16>()
+            .ok_or(errors::CryptoError::InvalidValue)?;
+        let (expires_at, rest) = rest
+            .split_first_chunk::<8>()
+            .ok_or(errors::CryptoError::InvalidValue)?;
+        let (key_version, rest) = rest
+            .split_first_chunk::<4>()
+            .ok_or(errors::CryptoError::InvalidValue)?;
+        let (identifier_len, rest) = rest
+            .split_first_chunk::<2>()
+            .ok_or(errors::CryptoError::InvalidValue)?;
+        let (identifier, private_key) = rest
+            .split_at_checked(usize::from(u16::from_be_bytes(*identifier_len)))
+            .ok_or(errors::CryptoError::InvalidValue)?;
+
+        Ok(Self {
+            id: *id,
+            algorithm: WrappingAlgorithm::from_id(*algorithm)
+                .ok_or(errors::CryptoError::InvalidValue)?,
+            expires_at: i64::from_be_bytes(*expires_at),
+            identifier: String::from_utf8(identifier.to_vec())
+                .change_context(errors::CryptoError::InvalidValue)?,
+            key_version: i32::from_be_bytes(*key_version),
+            private_key: StrongSecret::new(private_key.to_vec()),
+        })
+    }
+}
+
+fn generate_rsa() -> Result<WrappingKeyPair, Unspecified> {
+    let private_key = PrivateDecryptingKey::generate(KeySize::Rsa3072)?;
+    let public_key: PublicKeyX509Der<'_> = private_key.public_key().as_der()?;
+    let pkcs8: Pkcs8V1Der<'_> = private_key.as_der()?;
+
+    Ok(WrappingKeyPair {
+        public_key: public_key.as_ref().to_vec(),
+        private_key: StrongSecret::new(pkcs8.as_ref().to_vec()),
+    })
+}
+
+fn generate_x25519() -> Result<WrappingKeyPair, Unspecified> {
+    let private_key = agreement::PrivateKey::generate(&agreement::X25519)?;
+    let seed: Curve25519SeedBin<'_> = private_key.as_be_bytes()?;
+
+    Ok(WrappingKeyPair {
+        public_key: private_key.compute_public_key()?.as_ref().to_vec(),
+        private_key: StrongSecret::new(seed.as_ref().to_vec()),
+    })
+}
+
+fn unwrap_rsa(private_key: &[u8], wrapped_key: &[u8]) -> Result<Vec<u8>, Unspecified> {
+    let private_key = OaepPrivateDecryptingKey::new(
+        PrivateDecryptingKey::from_pkcs8(private_key).map_err(|_| Unspecified)?,
+    )?;
+    let mut output = vec![0_u8; private_key.min_output_size()];
+    let len = private_key
+        .decrypt(&OAEP_SHA256_MGF1SHA256, wrapped_key, &mut output, None)?
+        .len();
+    output.truncate(len);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_hpke_import_token_round_trip() {
+        let key_pair = WrappingAlgorithm::Hpke.generate().unwrap();
+        let wrapped = hpke::seal(&key_pair.public_key, b"", &[0x22; 32]).unwrap();
+
+        let id = ImportToken::generate_id().unwrap();
+        let token = ImportToken {
+            id,
+            algorithm: WrappingAlgorithm::Hpke,
+            expires_at: 1_700_000_000,
+            identifier: "User_user_12".to_string(),
+            key_version: 3,
+            private_key: key_pair.private_key,
+        };
+        let token = ImportToken::from_bytes(token.to_bytes().unwrap().peek()).unwrap();
+        assert_eq!(token.id, id);
+        assert_eq!(token.algorithm, WrappingAlgorithm::Hpke);
+        assert_eq!(token.expires_at, 1_700_000_000);
+        assert_eq!(token.identifier, "User_user_12");
+        assert_eq!(token.key_version, 3);
+
+        let key = token
+            .algorithm
+            .unwrap(&token.private_key, &wrapped)
+            .unwrap();
+        assert_eq!(key.peek(), &[0x22; 32]);
+    }
+}
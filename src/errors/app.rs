@@ -13,6 +13,7 @@ pub struct ApiErrorContainer {
 mod error_codes {
     pub const IE_00: &str = "IE_00";
     pub const BR_00: &str = "BR_00";
+    pub const BR_01: &str = "BR_01";
     pub const NF_00: &str = "NF_00";
 }
 
@@ -62,6 +63,8 @@ pub enum ApplicationErrorResponse {
     TenantIdNotFound,
     #[error("Tenant ID which was passed in the headers was invalid")]
     InvalidTenantId,
+    #[error("The data identifier {0} is not configured for the tenant")]
+    UnknownDataIdentifier(String),
 }
 
 impl<T> SwitchError<T, ApplicationErrorResponse> for super::CustomResult<T, ParsingError> {
@@ -179,6 +182,13 @@ impl IntoResponse for ApiErrorContainer {
                     error_code: error_codes::BR_00,
                 }),
             ),
+            err @ ApplicationErrorResponse::UnknownDataIdentifier(_) => (
+                StatusCode::BAD_REQUEST,
+                axum::Json(ApiErrorResponse {
+                    error_message: err.to_string(),
+                    error_code: error_codes::BR_01,
+                }),
+            ),
         }
         .into_response()
     }
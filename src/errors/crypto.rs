@@ -18,6 +18,8 @@ pub enum CryptoError {
     ParseError(String),
     #[error("Invalid value")]
     InvalidValue,
+    #[error("Leased key has expired or exhausted its usage limit")]
+    LeaseExhausted,
 }
 
 impl super::SwitchError<(), CryptoError> for Result<(), ring::error::Unspecified> {
@@ -1,4 +1,5 @@
 pub mod app;
+pub mod client;
 pub mod config;
 pub mod consts;
 pub mod core;
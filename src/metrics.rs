@@ -26,6 +26,9 @@ pub(crate) static ENCRYPTION_FAILURE: Lazy<Counter<u64>> =
 pub(crate) static DECRYPTION_FAILURE: Lazy<Counter<u64>> =
     Lazy::new(|| METER.u64_counter("DECRYPTION_FAILURE").build());
 
+pub(crate) static REENCRYPTION_FAILURE: Lazy<Counter<u64>> =
+    Lazy::new(|| METER.u64_counter("REENCRYPTION_FAILURE").build());
+
 pub(crate) static KEY_CREATE_FAILURE: Lazy<Counter<u64>> =
     Lazy::new(|| METER.u64_counter("KEY_CREATE_FAILURE").build());
 
@@ -45,3 +48,10 @@ pub(crate) static DECRYPTION_API_LATENCY: Lazy<Histogram<f64>> = Lazy::new(|| {
         .with_boundaries(Vec::from(duration_histogram_buckets()))
         .build()
 });
+
+pub(crate) static REENCRYPTION_API_LATENCY: Lazy<Histogram<f64>> = Lazy::new(|| {
+    METER
+        .f64_histogram("REENCRYPTION_API_LATENCY")
+        .with_boundaries(Vec::from(duration_histogram_buckets()))
+        .build()
+});
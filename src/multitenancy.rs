@@ -5,9 +5,10 @@ use hyper::header;
 use rustc_hash::FxHashMap;
 
 use crate::{
-    app::{AppState, SessionState, StorageState},
+    app::{AppState, SessionState},
     consts::TENANT_HEADER,
     errors::{self, ApiErrorContainer, SwitchError, ToContainerError},
+    storage::StorageState,
 };
 
 pub type MultiTenant<T> = FxHashMap<TenantId, T>;
@@ -11,6 +11,7 @@ impl Crypto {
         Router::new()
             .route("/encrypt", post(core::encrypt_data))
             .route("/decrypt", post(core::decrypt_data))
+            .route("/reencrypt", post(core::reencrypt_data))
             .with_state(state)
     }
 }
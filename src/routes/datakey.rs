@@ -10,7 +10,18 @@ impl DataKey {
         Router::new()
             .route("/create", post(core::create_data_key))
             .route("/rotate", post(core::rotate_data_key))
+            .route("/rollback", post(core::rollback_data_key))
             .route("/transfer", post(core::transfer_data_key))
+            .route(
+                "/import/wrapping-key",
+                post(core::create_import_wrapping_key),
+            )
+            .route("/import", post(core::import_data_key))
+            .route("/lease", post(core::lease_data_key))
+            .route("/wrap", post(core::wrap_data_key))
+            .route("/unwrap", post(core::unwrap_data_key))
+            .route("/tr31/import", post(core::import_tr31_key_block))
+            .route("/tr31/export", post(core::export_tr31_key_block))
             .with_state(state)
     }
 }
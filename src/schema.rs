@@ -1,5 +1,19 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    data_key_rollbacks (id) {
+        id -> Int4,
+        #[max_length = 255]
+        key_identifier -> Varchar,
+        #[max_length = 20]
+        data_identifier -> Varchar,
+        version -> Int4,
+        rolled_back_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -14,5 +28,16 @@ diesel::table! {
         created_at -> Timestamp,
         #[max_length = 30]
         source -> Varchar,
+        key_metadata -> Nullable<Text>,
+        #[max_length = 6]
+        kcv -> Nullable<Varchar>,
+        #[max_length = 255]
+        parent_key_identifier -> Nullable<Varchar>,
+        #[max_length = 20]
+        parent_data_identifier -> Nullable<Varchar>,
+        parent_version -> Nullable<Int4>,
+        is_active -> Bool,
     }
 }
+
+diesel::allow_tables_to_appear_in_same_query!(data_key_rollbacks, data_key_store,);
@@ -1,13 +1,21 @@
 pub(crate) mod adapter;
 pub(crate) mod cache;
+pub mod copy;
 pub(crate) mod dek;
 pub(crate) mod types;
 
-use diesel_async::{AsyncPgConnection, pooled_connection::bb8::PooledConnection};
+use diesel_async::{
+    AsyncPgConnection,
+    pooled_connection::bb8::{Pool, PooledConnection},
+};
+use scylla::client::caching_session::CachingSession;
 
-use self::adapter::{DbAdapter, DbAdapterType};
+use self::adapter::{
+    Cassandra, DbAdapter, DbAdapterType, DynamoDb, DynamoDbTable, Memory, MemoryStore, PgPools,
+    PostgreSQL, Sqlite, SqliteConnection,
+};
 use crate::{
-    config::Config,
+    config::{Config, StorageBackend},
     errors::{self, CustomResult},
 };
 
@@ -39,3 +47,37 @@ where
         <Self as DbAdapter>::get_conn(self).await
     }
 }
+
+/// Data key store of a tenant, chosen at runtime by its `StorageBackend`
+pub(crate) enum StorageState {
+    Postgres(DbState<PgPools, PostgreSQL>),
+    Cassandra(DbState<CachingSession, Cassandra>),
+    Memory(DbState<MemoryStore, Memory>),
+    Sqlite(DbState<Pool<SqliteConnection>, Sqlite>),
+    DynamoDb(DbState<DynamoDbTable, DynamoDb>),
+}
+
+impl StorageState {
+    /// # Panics
+    ///
+    /// Panics if unable to connect to the store
+    pub async fn from_config(config: &Config, backend: StorageBackend, schema: &str) -> Self {
+        match backend {
+            StorageBackend::Postgres => {
+                Self::Postgres(DbState::<PgPools, PostgreSQL>::from_config(config, schema).await)
+            }
+            StorageBackend::Cassandra => Self::Cassandra(
+                DbState::<CachingSession, Cassandra>::from_config(config, schema).await,
+            ),
+            StorageBackend::Memory => {
+                Self::Memory(DbState::<MemoryStore, Memory>::from_config(config, schema).await)
+            }
+            StorageBackend::Sqlite => Self::Sqlite(
+                DbState::<Pool<SqliteConnection>, Sqlite>::from_config(config, schema).await,
+            ),
+            StorageBackend::DynamoDb => Self::DynamoDb(
+                DbState::<DynamoDbTable, DynamoDb>::from_config(config, schema).await,
+            ),
+        }
+    }
+}
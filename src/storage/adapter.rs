@@ -1,16 +1,30 @@
 mod cassandra;
+mod dynamodb;
+mod memory;
 mod postgres;
+mod sqlite;
+
+pub use dynamodb::DynamoDbTable;
+pub use memory::MemoryStore;
+pub use postgres::PgPools;
+pub use sqlite::SqliteConnection;
 
 use crate::{config::Config, errors, storage::DbState};
 
 #[derive(Clone)]
 pub struct PostgreSQL;
 pub struct Cassandra;
+pub struct Memory;
+pub struct Sqlite;
+pub struct DynamoDb;
 
 pub trait DbAdapterType {}
 
 impl DbAdapterType for PostgreSQL {}
 impl DbAdapterType for Cassandra {}
+impl DbAdapterType for Memory {}
+impl DbAdapterType for Sqlite {}
+impl DbAdapterType for DynamoDb {}
 
 #[async_trait::async_trait]
 pub trait DbAdapter {
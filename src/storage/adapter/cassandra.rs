@@ -1,4 +1,11 @@
 mod dek;
+mod migrations;
+
+use error_stack::ResultExt;
+use scylla::{
+    response::query_result::QueryResult,
+    value::{CqlValue, Row},
+};
 
 use crate::storage::{Config, DbState, adapter::Cassandra, errors};
 
@@ -15,11 +22,20 @@ impl super::DbAdapter for DbState<scylla::client::caching_session::CachingSessio
             .pool_size(scylla::client::PoolSize::PerHost(
                 config.cassandra.pool_size,
             ))
-            .use_keyspace(schema, false)
             .build()
             .await
             .expect("Unable to build the cassandra Pool");
 
+        if config.cassandra.migrations.run_on_startup {
+            migrations::run(&session, schema, &config.cassandra.migrations)
+                .await
+                .expect("Failed to migrate the cassandra keyspace");
+        }
+        session
+            .use_keyspace(schema, false)
+            .await
+            .expect("Unable to use the cassandra keyspace");
+
         Self {
             _adapter: std::marker::PhantomData,
             pool: scylla::client::caching_session::CachingSession::from(
@@ -35,3 +51,20 @@ impl super::DbAdapter for DbState<scylla::client::caching_session::CachingSessio
         Ok(&self.pool)
     }
 }
+
+/// Whether a lightweight transaction was applied, which is the first column of its result
+fn is_applied(result: QueryResult) -> errors::CustomResult<bool, errors::DatabaseError> {
+    let row = result
+        .into_rows_result()
+        .change_context(errors::DatabaseError::Others)?
+        .rows::<Row>()
+        .change_context(errors::DatabaseError::Others)?
+        .next()
+        .transpose()
+        .change_context(errors::DatabaseError::Others)?;
+
+    Ok(matches!(
+        row.and_then(|row| row.columns.into_iter().next().flatten()),
+        Some(CqlValue::Boolean(true))
+    ))
+}
@@ -1,7 +1,18 @@
-use charybdis::{operations::Insert, options::Consistency};
-use error_stack::ResultExt;
+use charybdis::{operations::Find, options::Consistency};
+use error_stack::{IntoReport, ResultExt};
+use futures::{StreamExt, TryStreamExt, stream::BoxStream};
+use rustc_hash::FxHashSet;
+use scylla::{
+    client::caching_session::CachingSession,
+    statement::{
+        SerialConsistency,
+        batch::{Batch, BatchType},
+        unprepared::Statement,
+    },
+};
+use time::{OffsetDateTime, PrimitiveDateTime};
 
-use super::DbState;
+use super::{DbState, is_applied};
 use crate::{
     env::observability as logger,
     errors::{self, CustomResult, DatabaseError, SwitchError},
@@ -13,38 +24,134 @@ use crate::{
     types::{Identifier, key::Version},
 };
 
+/// Children are partitioned by their parent in a lookup table, as the parent columns of
+/// `data_key_store` are not part of its primary key
+const CHILDREN_QUERY: &str = "SELECT data_identifier, key_identifier FROM data_key_children WHERE parent_key_identifier = ? AND parent_data_identifier = ?";
+
+const INSERT_CHILD_QUERY: &str = "INSERT INTO data_key_children (parent_key_identifier, parent_data_identifier, data_identifier, key_identifier) VALUES (?, ?, ?, ?)";
+
+/// Restricting the clustering key with `IN` reads the versions from a single partition
+const KEYS_QUERY: &str = "SELECT * FROM data_key_store WHERE key_identifier = ? AND data_identifier = ? AND version IN ?";
+
+const ALL_KEYS_QUERY: &str = "SELECT * FROM data_key_store";
+
+const DEACTIVATE_QUERY: &str = "UPDATE data_key_store SET is_active = false WHERE key_identifier = ? AND data_identifier = ? AND version = ? IF EXISTS";
+
+/// Deactivates the latest version unless a version after the newest one was created, both rows
+/// are in the partition of the identifier. Deleting the absent next version leaves a tombstone
+/// that a later rotation's insert overrides.
+const ROLLBACK_BATCH: [&str; 2] = [
+    "UPDATE data_key_store SET is_active = false WHERE key_identifier = ? AND data_identifier = ? AND version = ? IF is_active != false",
+    "DELETE FROM data_key_store WHERE key_identifier = ? AND data_identifier = ? AND version = ? IF encryption_key = null",
+];
+
+const INSERT_ROLLBACK_QUERY: &str = "INSERT INTO data_key_rollbacks (bucket, rolled_back_at, data_identifier, key_identifier, version) VALUES (?, ?, ?, ?, ?)";
+
+const ROLLBACKS_QUERY: &str = "SELECT data_identifier, key_identifier FROM data_key_rollbacks WHERE bucket = ? AND rolled_back_at >= ?";
+
+/// The single partition of `data_key_rollbacks`
+const ROLLBACK_BUCKET: i32 = 0;
+
+const INSERT_IF_NOT_EXISTS_QUERY: &str = "INSERT INTO data_key_store (id, key_identifier, data_identifier, encryption_key, version, created_at, source, key_metadata, kcv, parent_key_identifier, parent_data_identifier, parent_version, is_active) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) IF NOT EXISTS";
+
+impl DbState<CachingSession, Cassandra> {
+    /// Inserts the key with a lightweight transaction, so that exactly one of concurrent inserts
+    /// of a version wins. Returns the winning key and whether it is `key`.
+    async fn insert_if_not_exists(
+        &self,
+        key: CassandraDataKey,
+        identifier: &Identifier,
+    ) -> CustomResult<(DataKey, bool), DatabaseError> {
+        let connection = self.get_conn().await.switch()?;
+
+        // Recorded before the key, so that no child key is missed when its parent is rotated. A
+        // lost insert leaves a row that at most rotates the child once more.
+        if let (Some(parent_key_identifier), Some(parent_data_identifier)) =
+            (&key.parent_key_identifier, &key.parent_data_identifier)
+        {
+            let mut statement = Statement::new(INSERT_CHILD_QUERY);
+            statement.set_consistency(Consistency::LocalQuorum);
+            connection
+                .execute_unpaged(
+                    statement,
+                    (
+                        parent_key_identifier,
+                        parent_data_identifier,
+                        &key.data_identifier,
+                        &key.key_identifier,
+                    ),
+                )
+                .await
+                .change_context(DatabaseError::Others)?;
+        }
+
+        // The commit of a lightweight transaction at EACH_QUORUM is rejected for keyspaces with
+        // SimpleStrategy, the serial consistency already orders it across data centers
+        let mut statement = Statement::new(INSERT_IF_NOT_EXISTS_QUERY);
+        statement.set_consistency(Consistency::Quorum);
+        statement.set_serial_consistency(Some(SerialConsistency::Serial));
+
+        let values = (
+            &key.id,
+            &key.key_identifier,
+            &key.data_identifier,
+            &key.encryption_key,
+            &key.version,
+            &key.created_at,
+            &key.source,
+            &key.key_metadata,
+            &key.kcv,
+            &key.parent_key_identifier,
+            &key.parent_data_identifier,
+            &key.parent_version,
+            &key.is_active,
+        );
+        let result = connection
+            .execute_unpaged(statement, values)
+            .await
+            .change_context(DatabaseError::Others)?;
+        if is_applied(result)? {
+            return Ok((DataKey::from(key), true));
+        }
+
+        // A serial read observes the key of the transaction that won, even before it is
+        // replicated to a quorum of the local data center
+        let winner = CassandraDataKey::find_by_key_identifier_and_data_identifier_and_version(
+            key.key_identifier,
+            key.data_identifier,
+            key.version,
+        )
+        .consistency(scylla::statement::Consistency::Serial)
+        .execute(connection)
+        .await
+        .switch()?;
+        logger::info!(lost_insert = %identifier, version = %winner.version);
+
+        Ok((DataKey::from(winner), false))
+    }
+}
+
 #[async_trait::async_trait]
-impl DataKeyStorageInterface
-    for DbState<scylla::client::caching_session::CachingSession, Cassandra>
-{
+impl DataKeyStorageInterface for DbState<CachingSession, Cassandra> {
     async fn get_or_insert_data_key(
         &self,
         new: DataKeyNew,
     ) -> CustomResult<DataKey, errors::DatabaseError> {
-        let connection = self.get_conn().await.switch()?;
         let key = CassandraDataKey::from(DataKey::from(new));
+        let identifier =
+            Identifier::try_from((key.data_identifier.clone(), key.key_identifier.clone()))
+                .change_context(errors::DatabaseError::Others)?;
 
-        let find_query = self
-            .get_key(
-                key.version,
-                &Identifier::try_from((key.data_identifier.clone(), key.key_identifier.clone()))
-                    .change_context(errors::DatabaseError::Others)?,
-            )
-            .await;
-
-        match find_query {
+        match self.get_key(key.version, &identifier).await {
             Ok(key) => Ok(key),
             Err(err) => {
-                if let DatabaseError::NotFound = err.current_context() {
+                if !matches!(err.current_context(), DatabaseError::NotFound) {
                     logger::error!(database_err=?err);
                 }
 
-                key.insert()
-                    .consistency(Consistency::EachQuorum)
-                    .execute(connection)
+                self.insert_if_not_exists(key, &identifier)
                     .await
-                    .switch()?;
-                Ok(DataKey::from(key))
+                    .map(|(data_key, _)| data_key)
             }
         }
     }
@@ -56,6 +163,55 @@ impl DataKeyStorageInterface
         let (data_id, key_id) = identifier.get_identifier();
         let connection = self.get_conn().await.switch()?;
 
+        // Versions are clustered in descending order and an identifier only has a handful of them
+        let data_keys: Vec<CassandraDataKey> =
+            CassandraDataKey::find_by_key_identifier_and_data_identifier(key_id, data_id)
+                .consistency(scylla::statement::Consistency::LocalQuorum)
+                .execute(connection)
+                .await
+                .switch()?
+                .try_collect()
+                .await
+                .switch()?;
+
+        data_keys
+            .into_iter()
+            .find(|data_key| data_key.is_active.unwrap_or(true))
+            .map(|data_key| data_key.version)
+            .ok_or(errors::DatabaseError::NotFound.into_report())
+    }
+
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError> {
+        let (data_id, key_id) = identifier.get_identifier();
+        let connection = self.get_conn().await.switch()?;
+
+        let data_keys: Vec<CassandraDataKey> =
+            CassandraDataKey::find_by_key_identifier_and_data_identifier(key_id, data_id)
+                .consistency(scylla::statement::Consistency::LocalQuorum)
+                .execute(connection)
+                .await
+                .switch()?
+                .try_collect()
+                .await
+                .switch()?;
+
+        Ok(data_keys
+            .into_iter()
+            .filter(|data_key| data_key.is_active.unwrap_or(true))
+            .map(|data_key| data_key.version)
+            .collect())
+    }
+
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        let (data_id, key_id) = identifier.get_identifier();
+        let connection = self.get_conn().await.switch()?;
+
         let data_key =
             CassandraDataKey::find_first_by_key_identifier_and_data_identifier(key_id, data_id)
                 .consistency(scylla::statement::Consistency::LocalQuorum)
@@ -66,6 +222,30 @@ impl DataKeyStorageInterface
         Ok(data_key.version)
     }
 
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let (data_id, key_id) = identifier.get_identifier();
+        let connection = self.get_conn().await.switch()?;
+
+        // Keys are inserted with lightweight transactions, so they are updated with one as well
+        let mut statement = Statement::new(DEACTIVATE_QUERY);
+        statement.set_consistency(Consistency::Quorum);
+        statement.set_serial_consistency(Some(SerialConsistency::Serial));
+
+        let result = connection
+            .execute_unpaged(statement, (key_id, data_id, v))
+            .await
+            .change_context(DatabaseError::Others)?;
+        if is_applied(result)? {
+            Ok(())
+        } else {
+            Err(DatabaseError::NotFound.into_report())
+        }
+    }
+
     async fn get_key(
         &self,
         v: Version,
@@ -84,4 +264,167 @@ impl DataKeyStorageInterface
 
         Ok(DataKey::from(data_key))
     }
+
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+        let (data_id, key_id) = identifier.get_identifier();
+        let connection = self.get_conn().await.switch()?;
+
+        let data_keys = CassandraDataKey::find(KEYS_QUERY, (key_id, data_id, versions.to_vec()))
+            .consistency(scylla::statement::Consistency::LocalQuorum)
+            .execute(connection)
+            .await
+            .switch()?
+            .try_collect()
+            .await
+            .switch()?;
+
+        Ok(data_keys.into_iter().map(DataKey::from).collect())
+    }
+
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+        let current = self.get_newest_version(identifier).await?;
+        if current != newest {
+            return Ok((self.get_key(current, identifier).await?, false));
+        }
+
+        // Rotations racing past the check above are settled by the lightweight transaction, the
+        // loser returns the winner's key
+        let key = CassandraDataKey::from(DataKey::from(new));
+        self.insert_if_not_exists(key, identifier).await
+    }
+
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let (data_id, key_id) = identifier.get_identifier();
+        let next = newest
+            .increment()
+            .change_context(DatabaseError::InvalidValue)?;
+        let connection = self.get_conn().await.switch()?;
+
+        // Recorded ahead of the rollback, other instances dropping their cached latest version
+        // for a rollback that does not apply is harmless
+        let mut statement = Statement::new(INSERT_ROLLBACK_QUERY);
+        statement.set_consistency(Consistency::Quorum);
+        connection
+            .execute_unpaged(
+                statement,
+                (
+                    ROLLBACK_BUCKET,
+                    OffsetDateTime::now_utc(),
+                    &data_id,
+                    &key_id,
+                    latest,
+                ),
+            )
+            .await
+            .change_context(DatabaseError::Others)?;
+
+        // Rotations insert the next version with a lightweight transaction in the same partition,
+        // so they are serialized with the conditional batch
+        let mut batch = Batch::new(BatchType::Logged);
+        ROLLBACK_BATCH
+            .into_iter()
+            .for_each(|query| batch.append_statement(query));
+        batch.set_consistency(Consistency::Quorum);
+        batch.set_serial_consistency(Some(SerialConsistency::Serial));
+
+        let result = connection
+            .batch(
+                &batch,
+                ((&key_id, &data_id, latest), (&key_id, &data_id, next)),
+            )
+            .await
+            .change_context(DatabaseError::Others)?;
+        if is_applied(result)? {
+            Ok(())
+        } else {
+            Err(DatabaseError::UniqueViolation.into_report())
+        }
+    }
+
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let connection = self.get_conn().await.switch()?;
+
+        let mut statement = Statement::new(ROLLBACKS_QUERY);
+        statement.set_consistency(Consistency::LocalQuorum);
+        let rolled_back: FxHashSet<(String, String)> = connection
+            .execute_iter(statement, (ROLLBACK_BUCKET, since.assume_utc()))
+            .await
+            .change_context(DatabaseError::Others)?
+            .rows_stream::<(String, String)>()
+            .change_context(DatabaseError::Others)?
+            .try_collect()
+            .await
+            .change_context(DatabaseError::Others)?;
+
+        rolled_back
+            .into_iter()
+            .map(|identifier| {
+                Identifier::try_from(identifier).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let (data_id, key_id) = parent.get_identifier();
+        let connection = self.get_conn().await.switch()?;
+
+        let mut statement = Statement::new(CHILDREN_QUERY);
+        statement.set_consistency(Consistency::LocalQuorum);
+        let children: Vec<(String, String)> = connection
+            .execute_iter(statement, (key_id, data_id))
+            .await
+            .change_context(DatabaseError::Others)?
+            .rows_stream::<(String, String)>()
+            .change_context(DatabaseError::Others)?
+            .try_collect()
+            .await
+            .change_context(DatabaseError::Others)?;
+
+        children
+            .into_iter()
+            .map(|child| {
+                Identifier::try_from(child).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    > {
+        let connection = self.get_conn().await.switch()?;
+
+        // The driver pages through the table while the stream is consumed
+        let data_keys = CassandraDataKey::find(ALL_KEYS_QUERY, ())
+            .consistency(scylla::statement::Consistency::LocalQuorum)
+            .execute(connection)
+            .await
+            .switch()?;
+
+        Ok(data_keys
+            .map(|data_key| data_key.map(DataKey::from).switch())
+            .boxed())
+    }
 }
@@ -0,0 +1,320 @@
+use std::time::{Duration, Instant};
+
+use error_stack::ResultExt;
+use rustc_hash::FxHashSet;
+use scylla::{
+    client::session::Session,
+    statement::{Consistency, SerialConsistency, unprepared::Statement},
+    value::CqlTimestamp,
+};
+
+use crate::{
+    config::CassandraMigrations,
+    env::observability as logger,
+    errors::{self, CustomResult},
+};
+
+/// Versioned CQL migrations of a tenant keyspace, applied in order
+const MIGRATIONS: [(i32, &str, &str); 7] = [
+    (
+        1,
+        "create_dek_table",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0001_create_dek_table.cql"
+        )),
+    ),
+    (
+        2,
+        "add_key_metadata_column",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0002_add_key_metadata_column.cql"
+        )),
+    ),
+    (
+        3,
+        "add_kcv_column",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0003_add_kcv_column.cql"
+        )),
+    ),
+    (
+        4,
+        "add_parent_key_columns",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0004_add_parent_key_columns.cql"
+        )),
+    ),
+    (
+        5,
+        "add_is_active_column",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0005_add_is_active_column.cql"
+        )),
+    ),
+    (
+        6,
+        "create_data_key_children_table",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0006_create_data_key_children_table.cql"
+        )),
+    ),
+    (
+        7,
+        "create_data_key_rollbacks_table",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/cassandra/migrations/0007_create_data_key_rollbacks_table.cql"
+        )),
+    ),
+];
+
+const CREATE_MIGRATIONS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (version int PRIMARY KEY, name text, applied_at timestamp)";
+const EXISTING_COLUMNS_QUERY: &str =
+    "SELECT column_name FROM system_schema.columns WHERE keyspace_name = ? AND table_name = ?";
+const APPLIED_MIGRATIONS_QUERY: &str = "SELECT version, applied_at FROM schema_migrations";
+/// A migration is claimed without `applied_at`, which is set once its statements ran
+const CLAIM_MIGRATION_QUERY: &str =
+    "INSERT INTO schema_migrations (version, name) VALUES (?, ?) IF NOT EXISTS";
+const RECORD_MIGRATION_QUERY: &str =
+    "UPDATE schema_migrations SET applied_at = toTimestamp(now()) WHERE version = ? IF EXISTS";
+const MIGRATION_STATUS_QUERY: &str = "SELECT applied_at FROM schema_migrations WHERE version = ?";
+
+/// How long an instance waits for a migration claimed by another one before applying it itself,
+/// which recovers a claim left behind by an instance that stopped while migrating
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(60);
+const CLAIM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Creates the keyspace if it is missing and applies the migrations it has not recorded in its
+/// `schema_migrations` table. Each migration is claimed with a lightweight transaction first, so
+/// that concurrently starting instances apply it once.
+pub(super) async fn run(
+    session: &Session,
+    keyspace: &str,
+    config: &CassandraMigrations,
+) -> CustomResult<(), errors::DatabaseError> {
+    // Keyspaces can not be bound, so the name is checked before it is formatted into the query
+    error_stack::ensure!(
+        !keyspace.is_empty()
+            && keyspace
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_'),
+        errors::DatabaseError::InvalidValue
+    );
+
+    session
+        .query_unpaged(
+            format!(
+                "CREATE KEYSPACE IF NOT EXISTS {keyspace} WITH replication = {}",
+                replication(config)
+            ),
+            (),
+        )
+        .await
+        .change_context(errors::DatabaseError::Others)
+        .attach("Failed to create the keyspace")?;
+    session
+        .use_keyspace(keyspace, false)
+        .await
+        .change_context(errors::DatabaseError::Others)?;
+
+    session
+        .query_unpaged(CREATE_MIGRATIONS_TABLE_QUERY, ())
+        .await
+        .change_context(errors::DatabaseError::Others)?;
+    let applied = session
+        .query_unpaged(APPLIED_MIGRATIONS_QUERY, ())
+        .await
+        .change_context(errors::DatabaseError::Others)?
+        .into_rows_result()
+        .change_context(errors::DatabaseError::Others)?
+        .rows::<(i32, Option<CqlTimestamp>)>()
+        .change_context(errors::DatabaseError::Others)?
+        .filter_map(|row| {
+            row.map(|(version, applied_at)| applied_at.map(|_| version))
+                .transpose()
+        })
+        .collect::<Result<FxHashSet<_>, _>>()
+        .change_context(errors::DatabaseError::Others)?;
+
+    for (version, name, cql) in MIGRATIONS {
+        if applied.contains(&version) {
+            continue;
+        }
+
+        // Instances starting at the same time race for each migration, only the one whose claim
+        // is applied runs its statements while the others wait for it
+        let mut claim = Statement::new(CLAIM_MIGRATION_QUERY);
+        claim.set_consistency(Consistency::Quorum);
+        claim.set_serial_consistency(Some(SerialConsistency::Serial));
+        let claimed = session
+            .query_unpaged(claim, (version, name))
+            .await
+            .change_context(errors::DatabaseError::Others)
+            .and_then(super::is_applied)?;
+        if !claimed && wait_until_applied(session, version).await? {
+            continue;
+        }
+        if !claimed {
+            logger::warn!(
+                keyspace,
+                migration = name,
+                "Taking over a stale migration claim"
+            );
+        }
+
+        for statement in cql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            // Columns added by an earlier attempt of the migration
+            if let Some((table, columns)) = added_columns(statement) {
+                let existing = existing_columns(session, keyspace, table).await?;
+                if columns.iter().all(|column| existing.contains(*column)) {
+                    logger::info!(keyspace, migration = name, "Columns already exist");
+                    continue;
+                }
+            }
+
+            session
+                .query_unpaged(statement, ())
+                .await
+                .change_context(errors::DatabaseError::Others)
+                .attach(name)?;
+        }
+
+        let mut record = Statement::new(RECORD_MIGRATION_QUERY);
+        record.set_consistency(Consistency::Quorum);
+        record.set_serial_consistency(Some(SerialConsistency::Serial));
+        session
+            .query_unpaged(record, (version,))
+            .await
+            .change_context(errors::DatabaseError::Others)?;
+        logger::info!(keyspace, migration = name, "Applied cassandra migration");
+    }
+
+    Ok(())
+}
+
+/// Polls a migration claimed by another instance, `false` if it is still not applied after
+/// [`CLAIM_TIMEOUT`]
+async fn wait_until_applied(
+    session: &Session,
+    version: i32,
+) -> CustomResult<bool, errors::DatabaseError> {
+    let started = Instant::now();
+    while started.elapsed() < CLAIM_TIMEOUT {
+        let mut status = Statement::new(MIGRATION_STATUS_QUERY);
+        status.set_consistency(Consistency::Serial);
+        let applied_at = session
+            .query_unpaged(status, (version,))
+            .await
+            .change_context(errors::DatabaseError::Others)?
+            .into_rows_result()
+            .change_context(errors::DatabaseError::Others)?
+            .maybe_first_row::<(Option<CqlTimestamp>,)>()
+            .change_context(errors::DatabaseError::Others)?
+            .and_then(|(applied_at,)| applied_at);
+        if applied_at.is_some() {
+            return Ok(true);
+        }
+        tokio::time::sleep(CLAIM_POLL_INTERVAL).await;
+    }
+
+    Ok(false)
+}
+
+/// Table and columns of an `ALTER TABLE ... ADD` statement
+fn added_columns(statement: &str) -> Option<(&str, Vec<&str>)> {
+    let (table, columns) = statement
+        .strip_prefix("ALTER TABLE ")?
+        .split_once(" ADD ")?;
+    let columns = columns
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .filter_map(|column| column.split_whitespace().next())
+        .collect();
+
+    Some((table.trim(), columns))
+}
+
+async fn existing_columns(
+    session: &Session,
+    keyspace: &str,
+    table: &str,
+) -> CustomResult<FxHashSet<String>, errors::DatabaseError> {
+    session
+        .query_unpaged(EXISTING_COLUMNS_QUERY, (keyspace, table))
+        .await
+        .change_context(errors::DatabaseError::Others)?
+        .into_rows_result()
+        .change_context(errors::DatabaseError::Others)?
+        .rows::<(String,)>()
+        .change_context(errors::DatabaseError::Others)?
+        .map(|row| row.map(|(column,)| column))
+        .collect::<Result<FxHashSet<_>, _>>()
+        .change_context(errors::DatabaseError::Others)
+}
+
+fn replication(config: &CassandraMigrations) -> String {
+    if config.replication.is_empty() {
+        return String::from("{'class': 'SimpleStrategy', 'replication_factor': 1}");
+    }
+
+    let mut data_centers = config
+        .replication
+        .iter()
+        .map(|(data_center, factor)| format!("'{data_center}': {factor}"))
+        .collect::<Vec<_>>();
+    data_centers.sort();
+    format!(
+        "{{'class': 'NetworkTopologyStrategy', {}}}",
+        data_centers.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication() {
+        let mut config = CassandraMigrations::default();
+        assert_eq!(
+            replication(&config),
+            "{'class': 'SimpleStrategy', 'replication_factor': 1}"
+        );
+
+        config.replication.insert(String::from("dc2"), 2);
+        config.replication.insert(String::from("dc1"), 3);
+        assert_eq!(
+            replication(&config),
+            "{'class': 'NetworkTopologyStrategy', 'dc1': 3, 'dc2': 2}"
+        );
+    }
+
+    #[test]
+    fn test_added_columns() {
+        assert_eq!(
+            added_columns("ALTER TABLE data_key_store ADD kcv text"),
+            Some(("data_key_store", vec!["kcv"]))
+        );
+        assert_eq!(
+            added_columns(
+                "ALTER TABLE data_key_store ADD (parent_key_identifier text, parent_version int)"
+            ),
+            Some((
+                "data_key_store",
+                vec!["parent_key_identifier", "parent_version"]
+            ))
+        );
+        assert_eq!(
+            added_columns("CREATE TABLE IF NOT EXISTS data_key_children (id int PRIMARY KEY)"),
+            None
+        );
+    }
+}
@@ -0,0 +1,147 @@
+mod dek;
+
+use std::time::Duration;
+
+use aws_config::{BehaviorVersion, meta::region::RegionProviderChain};
+use aws_sdk_dynamodb::{
+    Client,
+    client::Waiters,
+    config::Region,
+    types::{
+        AttributeDefinition, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType,
+        Projection, ProjectionType, ScalarAttributeType,
+    },
+};
+use error_stack::ResultExt;
+
+use crate::storage::{Config, DbState, adapter::DynamoDb, errors};
+
+/// Partition key attribute, the data and key identifiers of the key
+const PARTITION_KEY: &str = "identifier";
+/// Sort key attribute
+const SORT_KEY: &str = "version";
+/// Partition key of the parent of a key, only set on keys that have one
+const PARENT_KEY: &str = "parent";
+/// Index of the keys by their parent, for finding the children of a rotated key
+const PARENT_INDEX: &str = "parent_index";
+
+/// Longest wait for a created table to become active
+const TABLE_CREATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Key table of a tenant
+pub struct DynamoDbTable {
+    client: Client,
+    name: String,
+}
+
+#[async_trait::async_trait]
+impl super::DbAdapter for DbState<DynamoDbTable, DynamoDb> {
+    type Conn<'a> = &'a DynamoDbTable;
+    type AdapterType = DynamoDb;
+    type Pool = DynamoDbTable;
+
+    /// # Panics
+    ///
+    /// Panics if unable to create the table
+    #[allow(clippy::expect_used)]
+    async fn from_config(config: &Config, schema: &str) -> Self {
+        let dynamodb = &config.dynamodb;
+        let region_provider = RegionProviderChain::first_try(Region::new(dynamodb.region.clone()));
+        let mut sdk_config =
+            aws_config::defaults(BehaviorVersion::v2026_01_12()).region(region_provider);
+        if let Some(endpoint_url) = &dynamodb.endpoint_url {
+            sdk_config = sdk_config.endpoint_url(endpoint_url);
+        }
+
+        let table = DynamoDbTable {
+            client: Client::new(&sdk_config.load().await),
+            name: format!("{}{schema}", dynamodb.table_prefix),
+        };
+        if dynamodb.create_tables {
+            table
+                .create()
+                .await
+                .expect("Failed to create the dynamodb table");
+        }
+
+        Self {
+            _adapter: std::marker::PhantomData,
+            pool: table,
+        }
+    }
+
+    async fn get_conn<'a>(
+        &'a self,
+    ) -> errors::CustomResult<Self::Conn<'a>, errors::ConnectionError> {
+        Ok(&self.pool)
+    }
+}
+
+impl DynamoDbTable {
+    /// Creates the table if it does not exist and waits for it to become active
+    async fn create(&self) -> errors::CustomResult<(), errors::DatabaseError> {
+        let attribute = |name: &str, attribute_type| {
+            AttributeDefinition::builder()
+                .attribute_name(name)
+                .attribute_type(attribute_type)
+                .build()
+                .change_context(errors::DatabaseError::Others)
+        };
+        let key = |name: &str, key_type| {
+            KeySchemaElement::builder()
+                .attribute_name(name)
+                .key_type(key_type)
+                .build()
+                .change_context(errors::DatabaseError::Others)
+        };
+
+        let parent_index = GlobalSecondaryIndex::builder()
+            .index_name(PARENT_INDEX)
+            .key_schema(key(PARENT_KEY, KeyType::Hash)?)
+            .projection(
+                Projection::builder()
+                    .projection_type(ProjectionType::Include)
+                    .non_key_attributes("data_identifier")
+                    .non_key_attributes("key_identifier")
+                    .build(),
+            )
+            .build()
+            .change_context(errors::DatabaseError::Others)?;
+
+        let created = self
+            .client
+            .create_table()
+            .table_name(&self.name)
+            .attribute_definitions(attribute(PARTITION_KEY, ScalarAttributeType::S)?)
+            .attribute_definitions(attribute(SORT_KEY, ScalarAttributeType::N)?)
+            .attribute_definitions(attribute(PARENT_KEY, ScalarAttributeType::S)?)
+            .key_schema(key(PARTITION_KEY, KeyType::Hash)?)
+            .key_schema(key(SORT_KEY, KeyType::Range)?)
+            .global_secondary_indexes(parent_index)
+            .billing_mode(BillingMode::PayPerRequest)
+            .send()
+            .await;
+        match created {
+            Ok(_) => {}
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_resource_in_use_exception()) => {}
+            Err(err) => {
+                return Err(err)
+                    .change_context(errors::DatabaseError::Others)
+                    .attach("Failed to create the dynamodb table");
+            }
+        }
+
+        self.client
+            .wait_until_table_exists()
+            .table_name(&self.name)
+            .wait(TABLE_CREATION_TIMEOUT)
+            .await
+            .change_context(errors::DatabaseError::Others)
+            .attach("The dynamodb table did not become active")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,714 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use aws_sdk_dynamodb::{
+    primitives::Blob,
+    types::{AttributeValue, ConditionCheck, KeysAndAttributes, TransactWriteItem, Update},
+};
+use error_stack::{IntoReport, ResultExt};
+use futures::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+use hyperswitch_masking::{PeekInterface, StrongSecret};
+use rustc_hash::FxHashSet;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use super::{DynamoDbTable, PARENT_INDEX, PARENT_KEY, PARTITION_KEY, SORT_KEY};
+use crate::{
+    env::observability as logger,
+    errors::{self, CustomResult, DatabaseError, SwitchError},
+    storage::{
+        DbState,
+        adapter::DynamoDb,
+        dek::DataKeyStorageInterface,
+        types::{DataKey, DataKeyNew},
+    },
+    types::{Identifier, key::Version},
+};
+
+type Item = HashMap<String, AttributeValue>;
+
+/// Most keys a `BatchGetItem` request can read
+const BATCH_GET_LIMIT: usize = 100;
+
+/// Delay before reading unprocessed keys again, doubled on every further retry
+const BATCH_GET_BASE_DELAY: Duration = Duration::from_millis(50);
+const BATCH_GET_MAX_RETRIES: u32 = 8;
+
+/// Partition of the rollback items, which are sorted by the time of the rollback. Partition keys
+/// of identifiers start with a digit, so it never collides with one.
+const ROLLBACKS_PARTITION: &str = "rollbacks";
+
+/// Partition key of the identifier. The length prefix keeps it unambiguous whatever characters
+/// the identifiers contain.
+fn partition_key(data_identifier: &str, key_identifier: &str) -> AttributeValue {
+    AttributeValue::S(format!(
+        "{}:{data_identifier}:{key_identifier}",
+        data_identifier.len()
+    ))
+}
+
+fn primary_key(identifier: &Identifier, v: Version) -> Item {
+    let (d_id, k_id) = identifier.get_identifier();
+    HashMap::from([
+        (PARTITION_KEY.to_string(), partition_key(&d_id, &k_id)),
+        (
+            SORT_KEY.to_string(),
+            AttributeValue::N(v.inner().to_string()),
+        ),
+    ])
+}
+
+/// Row ids only exist in the SQL stores and are not kept
+fn to_item(data_key: DataKey) -> Item {
+    let parent = data_key
+        .parent_data_identifier
+        .as_deref()
+        .zip(data_key.parent_key_identifier.as_deref())
+        .map(|(data_identifier, key_identifier)| partition_key(data_identifier, key_identifier));
+
+    let mut item = HashMap::from([
+        (
+            PARTITION_KEY.to_string(),
+            partition_key(&data_key.data_identifier, &data_key.key_identifier),
+        ),
+        (
+            SORT_KEY.to_string(),
+            AttributeValue::N(data_key.version.inner().to_string()),
+        ),
+        (
+            "data_identifier".to_string(),
+            AttributeValue::S(data_key.data_identifier),
+        ),
+        (
+            "key_identifier".to_string(),
+            AttributeValue::S(data_key.key_identifier),
+        ),
+        (
+            "encryption_key".to_string(),
+            AttributeValue::B(Blob::new(data_key.encryption_key.peek().clone())),
+        ),
+        (
+            "created_at".to_string(),
+            AttributeValue::N(
+                data_key
+                    .created_at
+                    .assume_utc()
+                    .unix_timestamp_nanos()
+                    .to_string(),
+            ),
+        ),
+        ("source".to_string(), AttributeValue::S(data_key.source)),
+        (
+            "is_active".to_string(),
+            AttributeValue::Bool(data_key.is_active),
+        ),
+    ]);
+
+    let optional = [
+        (PARENT_KEY, parent),
+        ("key_metadata", data_key.key_metadata.map(AttributeValue::S)),
+        ("kcv", data_key.kcv.map(AttributeValue::S)),
+        (
+            "parent_key_identifier",
+            data_key.parent_key_identifier.map(AttributeValue::S),
+        ),
+        (
+            "parent_data_identifier",
+            data_key.parent_data_identifier.map(AttributeValue::S),
+        ),
+        (
+            "parent_version",
+            data_key
+                .parent_version
+                .map(|v| AttributeValue::N(v.inner().to_string())),
+        ),
+    ];
+    item.extend(
+        optional
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value?))),
+    );
+
+    item
+}
+
+fn string(item: &mut Item, name: &str) -> CustomResult<Option<String>, DatabaseError> {
+    item.remove(name)
+        .map(|value| match value {
+            AttributeValue::S(value) => Ok(value),
+            _ => Err(DatabaseError::InvalidValue.into_report()).attach(name.to_string()),
+        })
+        .transpose()
+}
+
+fn number<T: FromStr>(item: &mut Item, name: &str) -> CustomResult<Option<T>, DatabaseError> {
+    item.remove(name)
+        .map(|value| match value {
+            AttributeValue::N(value) => value
+                .parse()
+                .map_err(|_| DatabaseError::InvalidValue.into_report())
+                .attach(name.to_string()),
+            _ => Err(DatabaseError::InvalidValue.into_report()).attach(name.to_string()),
+        })
+        .transpose()
+}
+
+fn required<T>(value: Option<T>, name: &str) -> CustomResult<T, DatabaseError> {
+    value
+        .ok_or(DatabaseError::NotNullViolation.into_report())
+        .attach(name.to_string())
+}
+
+fn from_item(mut item: Item) -> CustomResult<DataKey, DatabaseError> {
+    let encryption_key = match item.remove("encryption_key") {
+        Some(AttributeValue::B(blob)) => StrongSecret::new(blob.into_inner()),
+        _ => {
+            return Err(DatabaseError::InvalidValue.into_report()).attach("encryption_key");
+        }
+    };
+    let created_at = OffsetDateTime::from_unix_timestamp_nanos(required(
+        number::<i128>(&mut item, "created_at")?,
+        "created_at",
+    )?)
+    .change_context(DatabaseError::InvalidValue)?;
+    let is_active = match item.remove("is_active") {
+        Some(AttributeValue::Bool(is_active)) => is_active,
+        _ => return Err(DatabaseError::InvalidValue.into_report()).attach("is_active"),
+    };
+
+    Ok(DataKey {
+        id: 0,
+        key_identifier: required(string(&mut item, "key_identifier")?, "key_identifier")?,
+        data_identifier: required(string(&mut item, "data_identifier")?, "data_identifier")?,
+        encryption_key,
+        version: Version::from(required(number::<i32>(&mut item, SORT_KEY)?, SORT_KEY)?),
+        created_at: PrimitiveDateTime::new(created_at.date(), created_at.time()),
+        source: required(string(&mut item, "source")?, "source")?,
+        key_metadata: string(&mut item, "key_metadata")?,
+        kcv: string(&mut item, "kcv")?,
+        parent_key_identifier: string(&mut item, "parent_key_identifier")?,
+        parent_data_identifier: string(&mut item, "parent_data_identifier")?,
+        parent_version: number::<i32>(&mut item, "parent_version")?.map(Version::from),
+        is_active,
+    })
+}
+
+impl DbState<DynamoDbTable, DynamoDb> {
+    /// Puts the key on the condition that the version does not exist, so that exactly one of
+    /// concurrent puts of a version wins. Returns the winning key and whether it is `data_key`.
+    async fn insert_if_not_exists(
+        &self,
+        data_key: DataKey,
+        identifier: &Identifier,
+    ) -> CustomResult<(DataKey, bool), DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+        let version = data_key.version;
+
+        let put = table
+            .client
+            .put_item()
+            .table_name(&table.name)
+            .set_item(Some(to_item(data_key.clone())))
+            .condition_expression("attribute_not_exists(#identifier)")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .send()
+            .await;
+        match put {
+            Ok(_) => Ok((data_key, true)),
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_conditional_check_failed_exception()) =>
+            {
+                let winner = self.get_key(version, identifier).await?;
+                logger::info!(lost_insert = %identifier, version = %winner.version);
+                Ok((winner, false))
+            }
+            Err(err) => Err(err).change_context(DatabaseError::Others),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl DataKeyStorageInterface for DbState<DynamoDbTable, DynamoDb> {
+    async fn get_or_insert_data_key(
+        &self,
+        new: DataKeyNew,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        let identifier =
+            Identifier::try_from((new.data_identifier.clone(), new.key_identifier.clone()))
+                .change_context(errors::DatabaseError::Others)?;
+
+        self.insert_if_not_exists(DataKey::from(new), &identifier)
+            .await
+            .map(|(data_key, _)| data_key)
+    }
+
+    async fn get_latest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+        let (d_id, k_id) = identifier.get_identifier();
+
+        // Filters are applied after the page is read, so pages are read newest first until an
+        // active version is found
+        let mut versions = table
+            .client
+            .query()
+            .table_name(&table.name)
+            .key_condition_expression("#identifier = :identifier")
+            .filter_expression("is_active = :active")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .expression_attribute_names("#version", SORT_KEY)
+            .expression_attribute_values(":identifier", partition_key(&d_id, &k_id))
+            .expression_attribute_values(":active", AttributeValue::Bool(true))
+            .projection_expression("#version")
+            .scan_index_forward(false)
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+
+        match versions.next().await {
+            Some(item) => required(
+                number::<i32>(&mut item.change_context(DatabaseError::Others)?, SORT_KEY)?,
+                SORT_KEY,
+            )
+            .map(Version::from),
+            None => Err(DatabaseError::NotFound.into_report()),
+        }
+    }
+
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+        let (d_id, k_id) = identifier.get_identifier();
+
+        let mut items = table
+            .client
+            .query()
+            .table_name(&table.name)
+            .key_condition_expression("#identifier = :identifier")
+            .filter_expression("is_active = :active")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .expression_attribute_names("#version", SORT_KEY)
+            .expression_attribute_values(":identifier", partition_key(&d_id, &k_id))
+            .expression_attribute_values(":active", AttributeValue::Bool(true))
+            .projection_expression("#version")
+            .scan_index_forward(false)
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+
+        let mut versions = Vec::new();
+        while let Some(item) = items.next().await {
+            let mut item = item.change_context(DatabaseError::Others)?;
+            versions.push(Version::from(required(
+                number::<i32>(&mut item, SORT_KEY)?,
+                SORT_KEY,
+            )?));
+        }
+
+        Ok(versions)
+    }
+
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+        let (d_id, k_id) = identifier.get_identifier();
+
+        let output = table
+            .client
+            .query()
+            .table_name(&table.name)
+            .key_condition_expression("#identifier = :identifier")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .expression_attribute_names("#version", SORT_KEY)
+            .expression_attribute_values(":identifier", partition_key(&d_id, &k_id))
+            .projection_expression("#version")
+            .scan_index_forward(false)
+            .consistent_read(true)
+            .limit(1)
+            .send()
+            .await
+            .change_context(DatabaseError::Others)?;
+
+        let mut item = output
+            .items
+            .and_then(|items| items.into_iter().next())
+            .ok_or(DatabaseError::NotFound.into_report())?;
+        required(number::<i32>(&mut item, SORT_KEY)?, SORT_KEY).map(Version::from)
+    }
+
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+
+        let updated = table
+            .client
+            .update_item()
+            .table_name(&table.name)
+            .set_key(Some(primary_key(identifier, v)))
+            .update_expression("SET is_active = :inactive")
+            .condition_expression("attribute_exists(#identifier)")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .expression_attribute_values(":inactive", AttributeValue::Bool(false))
+            .send()
+            .await;
+        match updated {
+            Ok(_) => Ok(()),
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_conditional_check_failed_exception()) =>
+            {
+                Err(DatabaseError::NotFound.into_report())
+            }
+            Err(err) => Err(err).change_context(DatabaseError::Others),
+        }
+    }
+
+    async fn get_key(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+
+        let output = table
+            .client
+            .get_item()
+            .table_name(&table.name)
+            .set_key(Some(primary_key(identifier, v)))
+            .consistent_read(true)
+            .send()
+            .await
+            .change_context(DatabaseError::Others)?;
+
+        from_item(output.item.ok_or(DatabaseError::NotFound.into_report())?)
+    }
+
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+
+        let mut data_keys = Vec::with_capacity(versions.len());
+        for chunk in versions.chunks(BATCH_GET_LIMIT) {
+            let keys = chunk
+                .iter()
+                .map(|v| primary_key(identifier, *v))
+                .collect::<Vec<_>>();
+            let mut request = Some(
+                KeysAndAttributes::builder()
+                    .set_keys(Some(keys))
+                    .consistent_read(true)
+                    .build()
+                    .change_context(DatabaseError::Others)?,
+            );
+
+            // Keys DynamoDB did not get to within the request are returned to be read again, which
+            // happens when the table is throttled, so the retries back off
+            let mut retries = 0;
+            while let Some(keys) = request.take() {
+                if retries > 0 {
+                    if retries > BATCH_GET_MAX_RETRIES {
+                        return Err(DatabaseError::Others.into_report())
+                            .attach("Keys remained unprocessed after the retries");
+                    }
+                    tokio::time::sleep(BATCH_GET_BASE_DELAY * 2_u32.pow(retries - 1)).await;
+                }
+                retries += 1;
+
+                let mut output = table
+                    .client
+                    .batch_get_item()
+                    .request_items(&table.name, keys)
+                    .send()
+                    .await
+                    .change_context(DatabaseError::Others)?;
+
+                for item in output
+                    .responses
+                    .as_mut()
+                    .and_then(|responses| responses.remove(&table.name))
+                    .unwrap_or_default()
+                {
+                    data_keys.push(from_item(item)?);
+                }
+                request = output
+                    .unprocessed_keys
+                    .and_then(|mut unprocessed| unprocessed.remove(&table.name))
+                    .filter(|keys| !keys.keys.is_empty());
+            }
+        }
+
+        Ok(data_keys)
+    }
+
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+        let current = self.get_newest_version(identifier).await?;
+        if current != newest {
+            return Ok((self.get_key(current, identifier).await?, false));
+        }
+
+        // Rotations racing past the check above are settled by the conditional put, the loser
+        // returns the winner's key
+        let data_key = DataKey::from(new);
+        self.insert_if_not_exists(data_key, identifier).await
+    }
+
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+        let (d_id, k_id) = identifier.get_identifier();
+        let next = newest
+            .increment()
+            .change_context(DatabaseError::InvalidValue)?;
+
+        // Recorded ahead of the rollback, other instances dropping their cached latest version
+        // for a rollback that does not apply is harmless
+        let rolled_back_at = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        table
+            .client
+            .put_item()
+            .table_name(&table.name)
+            .item(
+                PARTITION_KEY,
+                AttributeValue::S(ROLLBACKS_PARTITION.to_string()),
+            )
+            .item(SORT_KEY, AttributeValue::N(rolled_back_at.to_string()))
+            .item("data_identifier", AttributeValue::S(d_id))
+            .item("key_identifier", AttributeValue::S(k_id))
+            .item("rolled_back", AttributeValue::N(latest.inner().to_string()))
+            .send()
+            .await
+            .change_context(DatabaseError::Others)?;
+
+        // Rotations put the next version on the condition that it does not exist, the
+        // transaction checks the same item
+        let absent_next = ConditionCheck::builder()
+            .table_name(&table.name)
+            .set_key(Some(primary_key(identifier, next)))
+            .condition_expression("attribute_not_exists(#identifier)")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .build()
+            .change_context(DatabaseError::Others)?;
+        let deactivate = Update::builder()
+            .table_name(&table.name)
+            .set_key(Some(primary_key(identifier, latest)))
+            .update_expression("SET is_active = :inactive")
+            .condition_expression("is_active = :active")
+            .expression_attribute_values(":inactive", AttributeValue::Bool(false))
+            .expression_attribute_values(":active", AttributeValue::Bool(true))
+            .build()
+            .change_context(DatabaseError::Others)?;
+
+        let written = table
+            .client
+            .transact_write_items()
+            .transact_items(
+                TransactWriteItem::builder()
+                    .condition_check(absent_next)
+                    .build(),
+            )
+            .transact_items(TransactWriteItem::builder().update(deactivate).build())
+            .send()
+            .await;
+        match written {
+            Ok(_) => Ok(()),
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_transaction_canceled_exception()) =>
+            {
+                Err(DatabaseError::UniqueViolation.into_report())
+            }
+            Err(err) => Err(err).change_context(DatabaseError::Others),
+        }
+    }
+
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+
+        let mut items = table
+            .client
+            .query()
+            .table_name(&table.name)
+            .key_condition_expression("#identifier = :rollbacks AND #version >= :since")
+            .expression_attribute_names("#identifier", PARTITION_KEY)
+            .expression_attribute_names("#version", SORT_KEY)
+            .expression_attribute_values(
+                ":rollbacks",
+                AttributeValue::S(ROLLBACKS_PARTITION.to_string()),
+            )
+            .expression_attribute_values(
+                ":since",
+                AttributeValue::N(since.assume_utc().unix_timestamp_nanos().to_string()),
+            )
+            .projection_expression("data_identifier, key_identifier")
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+
+        let mut rolled_back = FxHashSet::default();
+        while let Some(item) = items.next().await {
+            let mut item = item.change_context(DatabaseError::Others)?;
+            rolled_back.insert((
+                required(string(&mut item, "data_identifier")?, "data_identifier")?,
+                required(string(&mut item, "key_identifier")?, "key_identifier")?,
+            ));
+        }
+
+        rolled_back
+            .into_iter()
+            .map(|identifier| {
+                Identifier::try_from(identifier).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    /// Keys are indexed by their parent, reads of the index are eventually consistent so a child
+    /// created moments before may be missed
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let table = self.get_conn().await.switch()?;
+        let (d_id, k_id) = parent.get_identifier();
+
+        let mut items = table
+            .client
+            .query()
+            .table_name(&table.name)
+            .index_name(PARENT_INDEX)
+            .key_condition_expression("#parent = :parent")
+            .expression_attribute_names("#parent", PARENT_KEY)
+            .expression_attribute_values(":parent", partition_key(&d_id, &k_id))
+            .projection_expression("data_identifier, key_identifier")
+            .into_paginator()
+            .items()
+            .send();
+
+        let mut children = FxHashSet::default();
+        while let Some(item) = items.next().await {
+            let mut item = item.change_context(DatabaseError::Others)?;
+            children.insert((
+                required(string(&mut item, "data_identifier")?, "data_identifier")?,
+                required(string(&mut item, "key_identifier")?, "key_identifier")?,
+            ));
+        }
+
+        children
+            .into_iter()
+            .map(|child| {
+                Identifier::try_from(child).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    > {
+        let table = self.get_conn().await.switch()?;
+
+        // The paginator scans the table while the stream is consumed, rollback items are left out
+        let items = table
+            .client
+            .scan()
+            .table_name(&table.name)
+            .filter_expression("attribute_exists(encryption_key)")
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+
+        Ok(stream::unfold(items, |mut items| async move {
+            let item = items.next().await?;
+            Some((
+                item.change_context(DatabaseError::Others)
+                    .and_then(from_item),
+                items,
+            ))
+        })
+        .boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_item_round_trip() {
+        let now = OffsetDateTime::now_utc();
+        let data_key = DataKey {
+            id: 0,
+            key_identifier: String::from("merchant:1"),
+            data_identifier: String::from("Merchant"),
+            encryption_key: StrongSecret::new(vec![7; 32]),
+            version: Version::from(3),
+            created_at: PrimitiveDateTime::new(now.date(), now.time()),
+            source: String::from("KMS"),
+            key_metadata: None,
+            kcv: Some(String::from("A1B2C3")),
+            parent_key_identifier: Some(String::from("org_1")),
+            parent_data_identifier: Some(String::from("Entity")),
+            parent_version: Some(Version::from(1)),
+            is_active: false,
+        };
+
+        let item = to_item(data_key.clone());
+        assert_eq!(
+            item.get(PARTITION_KEY),
+            Some(&AttributeValue::S(String::from("8:Merchant:merchant:1")))
+        );
+        assert_eq!(
+            item.get(PARENT_KEY),
+            Some(&AttributeValue::S(String::from("6:Entity:org_1")))
+        );
+        assert!(!item.contains_key("key_metadata"));
+
+        let decoded = from_item(item).unwrap();
+        assert_eq!(decoded.key_identifier, data_key.key_identifier);
+        assert_eq!(decoded.version, data_key.version);
+        assert_eq!(decoded.created_at, data_key.created_at);
+        assert_eq!(
+            decoded.encryption_key.peek(),
+            data_key.encryption_key.peek()
+        );
+        assert_eq!(decoded.kcv, data_key.kcv);
+        assert_eq!(decoded.parent_version, data_key.parent_version);
+        assert!(!decoded.is_active);
+    }
+}
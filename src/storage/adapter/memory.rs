@@ -0,0 +1,48 @@
+mod dek;
+
+use std::collections::BTreeMap;
+
+use time::PrimitiveDateTime;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::storage::{Config, DbState, adapter::Memory, errors, types::DataKey};
+
+/// Key versions of an identifier are stored next to each other, ordered by version
+type RowKey = (String, String, i32);
+
+/// Process local key store, its contents are lost when the process exits
+#[derive(Default)]
+pub struct MemoryStore {
+    rows: RwLock<Rows>,
+    /// Held by a rotation from reading the newest version until its key is inserted, and by a
+    /// rollback
+    rotation: Mutex<()>,
+}
+
+#[derive(Default)]
+struct Rows {
+    keys: BTreeMap<RowKey, DataKey>,
+    last_id: i32,
+    /// Data and key identifiers of the rolled back versions, with the time of the rollback
+    rollbacks: Vec<(String, String, PrimitiveDateTime)>,
+}
+
+#[async_trait::async_trait]
+impl super::DbAdapter for DbState<MemoryStore, Memory> {
+    type Conn<'a> = &'a MemoryStore;
+    type AdapterType = Memory;
+    type Pool = MemoryStore;
+
+    async fn from_config(_config: &Config, _schema: &str) -> Self {
+        Self {
+            _adapter: std::marker::PhantomData,
+            pool: MemoryStore::default(),
+        }
+    }
+
+    async fn get_conn<'a>(
+        &'a self,
+    ) -> errors::CustomResult<Self::Conn<'a>, errors::ConnectionError> {
+        Ok(&self.pool)
+    }
+}
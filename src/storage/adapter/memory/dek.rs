@@ -0,0 +1,386 @@
+use error_stack::{IntoReport, ResultExt};
+use futures::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use super::{MemoryStore, Rows};
+use crate::{
+    errors::{self, CustomResult},
+    storage::{
+        DbState,
+        adapter::Memory,
+        dek::DataKeyStorageInterface,
+        types::{DataKey, DataKeyNew},
+    },
+    types::{Identifier, key::Version},
+};
+
+impl Rows {
+    /// Key versions of the identifier, oldest first
+    fn versions(&self, identifier: &Identifier) -> impl DoubleEndedIterator<Item = &DataKey> {
+        let (d_id, k_id) = identifier.get_identifier();
+        self.keys
+            .range((d_id.clone(), k_id.clone(), i32::MIN)..=(d_id, k_id, i32::MAX))
+            .map(|(_, data_key)| data_key)
+    }
+
+    fn get(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<&DataKey, errors::DatabaseError> {
+        let (d_id, k_id) = identifier.get_identifier();
+        self.keys
+            .get(&(d_id, k_id, v.inner()))
+            .ok_or(errors::DatabaseError::NotFound.into_report())
+    }
+
+    /// Fails with a unique violation if the version is stored already, like the primary key of
+    /// the Postgres table
+    fn insert(&mut self, new: DataKeyNew) -> CustomResult<DataKey, errors::DatabaseError> {
+        let row_key = (
+            new.data_identifier.clone(),
+            new.key_identifier.clone(),
+            new.version.inner(),
+        );
+        if self.keys.contains_key(&row_key) {
+            return Err(errors::DatabaseError::UniqueViolation.into_report());
+        }
+
+        self.last_id += 1;
+        let data_key = DataKey {
+            id: self.last_id,
+            ..DataKey::from(new)
+        };
+        self.keys.insert(row_key, data_key.clone());
+        Ok(data_key)
+    }
+}
+
+#[async_trait::async_trait]
+impl DataKeyStorageInterface for DbState<MemoryStore, Memory> {
+    async fn get_or_insert_data_key(
+        &self,
+        new: DataKeyNew,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        let row_key = (
+            new.data_identifier.clone(),
+            new.key_identifier.clone(),
+            new.version.inner(),
+        );
+
+        let mut rows = self.pool.rows.write().await;
+        match rows.insert(new) {
+            Ok(result) => Ok(result),
+            Err(err) => match err.current_context() {
+                errors::DatabaseError::UniqueViolation => rows
+                    .keys
+                    .get(&row_key)
+                    .cloned()
+                    .ok_or(errors::DatabaseError::NotFound.into_report()),
+                _ => Err(err),
+            },
+        }
+    }
+
+    async fn get_latest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        self.pool
+            .rows
+            .read()
+            .await
+            .versions(identifier)
+            .rev()
+            .find(|data_key| data_key.is_active)
+            .map(|data_key| data_key.version)
+            .ok_or(errors::DatabaseError::NotFound.into_report())
+    }
+
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError> {
+        Ok(self
+            .pool
+            .rows
+            .read()
+            .await
+            .versions(identifier)
+            .rev()
+            .filter(|data_key| data_key.is_active)
+            .map(|data_key| data_key.version)
+            .collect())
+    }
+
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        self.pool
+            .rows
+            .read()
+            .await
+            .versions(identifier)
+            .next_back()
+            .map(|data_key| data_key.version)
+            .ok_or(errors::DatabaseError::NotFound.into_report())
+    }
+
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let (d_id, k_id) = identifier.get_identifier();
+        let mut rows = self.pool.rows.write().await;
+        let data_key = rows
+            .keys
+            .get_mut(&(d_id, k_id, v.inner()))
+            .ok_or(errors::DatabaseError::NotFound.into_report())?;
+        data_key.is_active = false;
+
+        Ok(())
+    }
+
+    async fn get_key(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        self.pool.rows.read().await.get(v, identifier).cloned()
+    }
+
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+        Ok(self
+            .pool
+            .rows
+            .read()
+            .await
+            .versions(identifier)
+            .filter(|data_key| versions.contains(&data_key.version))
+            .cloned()
+            .collect())
+    }
+
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+        let _rotation = self.pool.rotation.lock().await;
+
+        let current = self.get_newest_version(identifier).await?;
+        if current != newest {
+            return Ok((self.get_key(current, identifier).await?, false));
+        }
+
+        let data_key = self.pool.rows.write().await.insert(new)?;
+        Ok((data_key, true))
+    }
+
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let _rotation = self.pool.rotation.lock().await;
+
+        let current = self.get_newest_version(identifier).await?;
+        error_stack::ensure!(current == newest, errors::DatabaseError::UniqueViolation);
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let mut rows = self.pool.rows.write().await;
+        let data_key = rows
+            .keys
+            .get_mut(&(d_id.clone(), k_id.clone(), latest.inner()))
+            .filter(|data_key| data_key.is_active)
+            .ok_or(errors::DatabaseError::UniqueViolation.into_report())?;
+        data_key.is_active = false;
+
+        let now = OffsetDateTime::now_utc();
+        rows.rollbacks
+            .push((d_id, k_id, PrimitiveDateTime::new(now.date(), now.time())));
+
+        Ok(())
+    }
+
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let rows = self.pool.rows.read().await;
+
+        let mut rolled_back = rows
+            .rollbacks
+            .iter()
+            .filter(|(_, _, rolled_back_at)| *rolled_back_at >= since)
+            .map(|(d_id, k_id, _)| (d_id.clone(), k_id.clone()))
+            .collect::<Vec<_>>();
+        rolled_back.sort();
+        rolled_back.dedup();
+
+        rolled_back
+            .into_iter()
+            .map(|identifier| {
+                Identifier::try_from(identifier).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let (d_id, k_id) = parent.get_identifier();
+        let rows = self.pool.rows.read().await;
+
+        let mut children = rows
+            .keys
+            .values()
+            .filter(|data_key| {
+                data_key.parent_data_identifier.as_ref() == Some(&d_id)
+                    && data_key.parent_key_identifier.as_ref() == Some(&k_id)
+            })
+            .map(|data_key| {
+                (
+                    data_key.data_identifier.clone(),
+                    data_key.key_identifier.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // Versions of an identifier are adjacent in the map
+        children.dedup();
+
+        children
+            .into_iter()
+            .map(|child| {
+                Identifier::try_from(child).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    > {
+        // A snapshot, so that the lock is not held while the stream is consumed
+        let keys = self
+            .pool
+            .rows
+            .read()
+            .await
+            .keys
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        Ok(stream::iter(keys.into_iter().map(Ok)).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use hyperswitch_masking::StrongSecret;
+
+    use super::*;
+
+    fn new_key(identifier: &Identifier, version: i32) -> DataKeyNew {
+        let (data_identifier, key_identifier) = identifier.get_identifier();
+        let now = time::OffsetDateTime::now_utc();
+        DataKeyNew {
+            key_identifier,
+            data_identifier,
+            encryption_key: StrongSecret::new(vec![u8::try_from(version).unwrap(); 32]),
+            version: Version::from(version),
+            created_at: time::PrimitiveDateTime::new(now.date(), now.time()),
+            source: String::from("KMS"),
+            key_metadata: None,
+            kcv: None,
+            parent_key_identifier: None,
+            parent_data_identifier: None,
+            parent_version: None,
+            is_active: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let db = DbState::<MemoryStore, Memory> {
+            pool: MemoryStore::default(),
+            _adapter: std::marker::PhantomData,
+        };
+        let identifier = Identifier::new(String::from("User"), String::from("user_1"));
+
+        db.get_or_insert_data_key(new_key(&identifier, 1))
+            .await
+            .unwrap();
+        db.get_or_insert_data_key(new_key(&identifier, 2))
+            .await
+            .unwrap();
+
+        let mut duplicate = new_key(&identifier, 2);
+        duplicate.encryption_key = StrongSecret::new(vec![0; 32]);
+        let existing = db.get_or_insert_data_key(duplicate).await.unwrap();
+        assert_eq!(existing.id, 2);
+        assert!(matches!(
+            db.pool
+                .rows
+                .write()
+                .await
+                .insert(new_key(&identifier, 2))
+                .unwrap_err()
+                .current_context(),
+            errors::DatabaseError::UniqueViolation
+        ));
+
+        db.deactivate_version(Version::from(2), &identifier)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_latest_version(&identifier).await.unwrap(),
+            Version::from(1)
+        );
+        assert_eq!(
+            db.get_newest_version(&identifier).await.unwrap(),
+            Version::from(2)
+        );
+
+        let before = time::OffsetDateTime::now_utc();
+        let before = time::PrimitiveDateTime::new(before.date(), before.time());
+        db.get_or_insert_data_key(new_key(&identifier, 3))
+            .await
+            .unwrap();
+        assert!(matches!(
+            db.rollback_version(&identifier, Version::from(2), Version::from(3))
+                .await
+                .unwrap_err()
+                .current_context(),
+            errors::DatabaseError::UniqueViolation
+        ));
+        db.rollback_version(&identifier, Version::from(3), Version::from(3))
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_latest_version(&identifier).await.unwrap(),
+            Version::from(1)
+        );
+        assert_eq!(
+            db.get_rolled_back(before).await.unwrap(),
+            vec![identifier.clone()]
+        );
+    }
+}
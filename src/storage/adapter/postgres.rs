@@ -1,4 +1,5 @@
 mod dek;
+mod migrations;
 
 #[cfg(feature = "postgres_ssl")]
 use diesel::ConnectionError;
@@ -9,13 +10,22 @@ use diesel_async::{
 use error_stack::ResultExt;
 use hyperswitch_masking::PeekInterface;
 
-use crate::storage::{Config, Connection, DbState, adapter::PostgreSQL, errors};
+use crate::{
+    env::observability as logger,
+    storage::{Config, Connection, DbState, adapter::PostgreSQL, errors},
+};
+
+/// Connections of a tenant schema to the primary and to the optional read replica
+pub struct PgPools {
+    primary: Pool<AsyncPgConnection>,
+    replica: Option<Pool<AsyncPgConnection>>,
+}
 
 #[async_trait::async_trait]
-impl super::DbAdapter for DbState<Pool<AsyncPgConnection>, PostgreSQL> {
+impl super::DbAdapter for DbState<PgPools, PostgreSQL> {
     type Conn<'a> = Connection<'a>;
     type AdapterType = PostgreSQL;
-    type Pool = Pool<AsyncPgConnection>;
+    type Pool = PgPools;
 
     /// # Panics
     ///
@@ -23,70 +33,45 @@ impl super::DbAdapter for DbState<Pool<AsyncPgConnection>, PostgreSQL> {
     #[allow(clippy::expect_used)]
     async fn from_config(config: &Config, schema: &str) -> Self {
         let database = &config.database;
-        let password = database.password.expose(config).await;
-        let database_url = format!(
-            "postgres://{}:{}@{}:{}/{}?application_name={}&options=-c search_path%3D{}",
-            database.user.peek(),
-            password.peek(),
-            database.host,
-            database.port,
-            database.dbname.peek(),
+        let primary = build_pool(
+            config,
             schema,
-            schema
-        );
-
-        #[cfg(not(feature = "postgres_ssl"))]
-        let mgr_config = ManagerConfig::default();
-
-        #[cfg(feature = "postgres_ssl")]
-        let mut mgr_config = ManagerConfig::default();
-
-        #[cfg(feature = "postgres_ssl")]
-        if database.enable_ssl == Some(true) {
-            let root_ca = database
-                .root_ca
-                .clone()
-                .expect("Failed to load db server root cert from the config")
-                .expose(config)
-                .await;
-            mgr_config.custom_setup = Box::new(move |config: &str| {
-                Box::pin({
-                    let root_ca = root_ca.clone();
-                    async move {
-                        let mut root_certificate = rustls::RootCertStore::empty();
-                        for cert in rustls_pemfile::certs(&mut root_ca.peek().as_ref()) {
-                            root_certificate
-                                .add(cert.expect("Failed to load db server root cert"))
-                                .expect("Failed to add cert to RootCertStore");
-                        }
-                        let rustls_config = rustls::ClientConfig::builder()
-                            .with_root_certificates(root_certificate)
-                            .with_no_client_auth();
-                        let tls = tokio_postgres_rustls::MakeRustlsConnect::new(rustls_config);
-                        let (client, conn) = tokio_postgres::connect(config, tls)
-                            .await
-                            .map_err(|e| ConnectionError::BadConnection(e.to_string()))?;
-
-                        AsyncPgConnection::try_from_client_and_connection(client, conn).await
-                    }
-                })
-            });
+            &database.host,
+            database.port,
+            database.pool_size,
+            database.min_idle,
+        )
+        .await;
+
+        // The connection's search path is the tenant schema, so the migrations apply to it
+        if database.migrations.run_on_startup {
+            let mut connection = primary
+                .get()
+                .await
+                .expect("Failed to get a connection to migrate the schema");
+            migrations::run(&mut connection, schema, database.migrations.dry_run)
+                .await
+                .expect("Failed to migrate the schema");
         }
 
-        let mgr = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
-            database_url,
-            mgr_config,
-        );
-        let pool = Pool::builder()
-            .max_size(database.pool_size.unwrap_or(10))
-            .min_idle(database.min_idle)
-            .build(mgr)
-            .await
-            .expect("Failed to establish pool connection");
+        let replica = match &database.replica {
+            Some(replica) => Some(
+                build_pool(
+                    config,
+                    schema,
+                    &replica.host,
+                    replica.port,
+                    replica.pool_size,
+                    replica.min_idle,
+                )
+                .await,
+            ),
+            None => None,
+        };
 
         Self {
             _adapter: std::marker::PhantomData,
-            pool,
+            pool: PgPools { primary, replica },
         }
     }
 
@@ -94,8 +79,98 @@ impl super::DbAdapter for DbState<Pool<AsyncPgConnection>, PostgreSQL> {
         &'a self,
     ) -> errors::CustomResult<Self::Conn<'a>, errors::ConnectionError> {
         self.pool
+            .primary
             .get()
             .await
             .change_context(errors::ConnectionError::ConnectionEstablishFailed)
     }
 }
+
+impl DbState<PgPools, PostgreSQL> {
+    /// Connection to the read replica, `None` when no replica is configured or none of its
+    /// connections is available, reads then go to the primary
+    async fn get_replica_conn(&self) -> Option<Connection<'_>> {
+        let replica = self.pool.replica.as_ref()?;
+
+        replica
+            .get()
+            .await
+            .inspect_err(|err| logger::warn!(replica_err = ?err, "Replica connection failed"))
+            .ok()
+    }
+}
+
+/// # Panics
+///
+/// Panics if unable to connect to Database
+#[allow(clippy::expect_used)]
+async fn build_pool(
+    config: &Config,
+    schema: &str,
+    host: &str,
+    port: u16,
+    pool_size: Option<u32>,
+    min_idle: Option<u32>,
+) -> Pool<AsyncPgConnection> {
+    let database = &config.database;
+    let password = database.password.expose(config).await;
+    let database_url = format!(
+        "postgres://{}:{}@{}:{}/{}?application_name={}&options=-c search_path%3D{}",
+        database.user.peek(),
+        password.peek(),
+        host,
+        port,
+        database.dbname.peek(),
+        schema,
+        schema
+    );
+
+    #[cfg(not(feature = "postgres_ssl"))]
+    let mgr_config = ManagerConfig::default();
+
+    #[cfg(feature = "postgres_ssl")]
+    let mut mgr_config = ManagerConfig::default();
+
+    #[cfg(feature = "postgres_ssl")]
+    if database.enable_ssl == Some(true) {
+        let root_ca = database
+            .root_ca
+            .clone()
+            .expect("Failed to load db server root cert from the config")
+            .expose(config)
+            .await;
+        mgr_config.custom_setup = Box::new(move |config: &str| {
+            Box::pin({
+                let root_ca = root_ca.clone();
+                async move {
+                    let mut root_certificate = rustls::RootCertStore::empty();
+                    for cert in rustls_pemfile::certs(&mut root_ca.peek().as_ref()) {
+                        root_certificate
+                            .add(cert.expect("Failed to load db server root cert"))
+                            .expect("Failed to add cert to RootCertStore");
+                    }
+                    let rustls_config = rustls::ClientConfig::builder()
+                        .with_root_certificates(root_certificate)
+                        .with_no_client_auth();
+                    let tls = tokio_postgres_rustls::MakeRustlsConnect::new(rustls_config);
+                    let (client, conn) = tokio_postgres::connect(config, tls)
+                        .await
+                        .map_err(|e| ConnectionError::BadConnection(e.to_string()))?;
+
+                    AsyncPgConnection::try_from_client_and_connection(client, conn).await
+                }
+            })
+        });
+    }
+
+    let mgr = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        database_url,
+        mgr_config,
+    );
+    Pool::builder()
+        .max_size(pool_size.unwrap_or(10))
+        .min_idle(min_idle)
+        .build(mgr)
+        .await
+        .expect("Failed to establish pool connection")
+}
@@ -1,11 +1,17 @@
 use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, associations::HasTable};
-use diesel_async::{AsyncPgConnection, RunQueryDsl, pooled_connection::bb8::Pool};
-use error_stack::ResultExt;
+use diesel_async::{AnsiTransactionManager, AsyncPgConnection, RunQueryDsl, TransactionManager};
+use error_stack::{IntoReport, ResultExt};
+use futures::{
+    StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
+use time::{OffsetDateTime, PrimitiveDateTime};
 
-use super::DbState;
+use super::{DbState, PgPools};
 use crate::{
+    env::observability as logger,
     errors::{self, CustomResult, SwitchError},
-    schema::data_key_store::*,
+    schema::{data_key_rollbacks, data_key_store::*},
     storage::{
         adapter::PostgreSQL,
         dek::DataKeyStorageInterface,
@@ -14,8 +20,11 @@ use crate::{
     types::{Identifier, key::Version},
 };
 
+/// Rows read at a time when streaming the whole table
+const STREAM_PAGE_SIZE: i64 = 1000;
+
 #[async_trait::async_trait]
-impl DataKeyStorageInterface for DbState<Pool<AsyncPgConnection>, PostgreSQL> {
+impl DataKeyStorageInterface for DbState<PgPools, PostgreSQL> {
     async fn get_or_insert_data_key(
         &self,
         new: DataKeyNew,
@@ -48,6 +57,48 @@ impl DataKeyStorageInterface for DbState<Pool<AsyncPgConnection>, PostgreSQL> {
     async fn get_latest_version(
         &self,
         identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        // Read from the primary, a lagging replica still has versions that were rotated past or
+        // rolled back
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = DataKey::table()
+            .select(version)
+            .order_by(version.desc())
+            .filter(
+                data_identifier
+                    .eq(d_id)
+                    .and(key_identifier.eq(k_id))
+                    .and(is_active.eq(true)),
+            );
+
+        query.get_result(&mut connection).await.switch()
+    }
+
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = DataKey::table()
+            .select(version)
+            .order_by(version.desc())
+            .filter(
+                data_identifier
+                    .eq(d_id)
+                    .and(key_identifier.eq(k_id))
+                    .and(is_active.eq(true)),
+            );
+
+        query.load(&mut connection).await.switch()
+    }
+
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
     ) -> CustomResult<Version, errors::DatabaseError> {
         let mut connection = self.get_conn().await.switch()?;
 
@@ -60,20 +111,326 @@ impl DataKeyStorageInterface for DbState<Pool<AsyncPgConnection>, PostgreSQL> {
         query.get_result(&mut connection).await.switch()
     }
 
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = diesel::update(DataKey::table())
+            .filter(
+                version
+                    .eq(v)
+                    .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+            )
+            .set(is_active.eq(false));
+
+        match query.execute(&mut connection).await.switch()? {
+            0 => Err(errors::DatabaseError::NotFound.into_report()),
+            _ => Ok(()),
+        }
+    }
+
     async fn get_key(
         &self,
         v: Version,
         identifier: &Identifier,
     ) -> CustomResult<DataKey, errors::DatabaseError> {
+        // Keys created moments before may not be replicated yet. The stored key of a version
+        // never changes, but its `is_active` flag may be stale on the replica.
+        if let Some(mut connection) = self.get_replica_conn().await {
+            match select_key(&mut connection, v, identifier).await {
+                Err(err) if matches!(err.current_context(), errors::DatabaseError::NotFound) => {}
+                Err(err) => logger::warn!(replica_err = ?err, "Falling back to the primary"),
+                result => return result,
+            }
+        }
+
         let mut connection = self.get_conn().await.switch()?;
+        select_key(&mut connection, v, identifier).await
+    }
 
-        let (d_id, k_id) = identifier.get_identifier();
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+        if let Some(mut connection) = self.get_replica_conn().await {
+            match select_keys(&mut connection, identifier, versions).await {
+                Ok(data_keys) if data_keys.len() == versions.len() => return Ok(data_keys),
+                Ok(_) => {}
+                Err(err) => logger::warn!(replica_err = ?err, "Falling back to the primary"),
+            }
+        }
 
-        let query = DataKey::table().filter(
-            version
-                .eq(v)
-                .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
-        );
-        query.get_result(&mut connection).await.switch()
+        let mut connection = self.get_conn().await.switch()?;
+        select_keys(&mut connection, identifier, versions).await
     }
+
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+        let connection: &mut AsyncPgConnection = &mut connection;
+
+        AnsiTransactionManager::begin_transaction(connection)
+            .await
+            .switch()?;
+        let rotated = rotate_locked(connection, identifier, newest, new).await;
+        match rotated {
+            Ok(_) => AnsiTransactionManager::commit_transaction(connection)
+                .await
+                .switch()?,
+            Err(_) => AnsiTransactionManager::rollback_transaction(connection)
+                .await
+                .switch()?,
+        }
+
+        rotated
+    }
+
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+        let connection: &mut AsyncPgConnection = &mut connection;
+
+        AnsiTransactionManager::begin_transaction(connection)
+            .await
+            .switch()?;
+        let rolled_back = rollback_locked(connection, identifier, newest, latest).await;
+        match rolled_back {
+            Ok(_) => AnsiTransactionManager::commit_transaction(connection)
+                .await
+                .switch()?,
+            Err(_) => AnsiTransactionManager::rollback_transaction(connection)
+                .await
+                .switch()?,
+        }
+
+        rolled_back
+    }
+
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let rolled_back: Vec<(String, String)> = data_key_rollbacks::table
+            .select((
+                data_key_rollbacks::data_identifier,
+                data_key_rollbacks::key_identifier,
+            ))
+            .filter(data_key_rollbacks::rolled_back_at.ge(since))
+            .distinct()
+            .load(&mut connection)
+            .await
+            .switch()?;
+
+        rolled_back
+            .into_iter()
+            .map(|identifier| {
+                Identifier::try_from(identifier).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = parent.get_identifier();
+
+        let query = DataKey::table()
+            .select((data_identifier, key_identifier))
+            .filter(
+                parent_data_identifier
+                    .eq(d_id)
+                    .and(parent_key_identifier.eq(k_id)),
+            )
+            .distinct();
+        let children: Vec<(String, String)> = query.load(&mut connection).await.switch()?;
+
+        children
+            .into_iter()
+            .map(|child| {
+                Identifier::try_from(child).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    > {
+        // Pages by primary key, so that rows inserted while streaming do not shift the pages
+        let pages = stream::try_unfold(0, move |last_id| async move {
+            let mut connection = self.get_conn().await.switch()?;
+            let page: Vec<DataKey> = DataKey::table()
+                .filter(id.gt(last_id))
+                .order_by(id.asc())
+                .limit(STREAM_PAGE_SIZE)
+                .load(&mut connection)
+                .await
+                .switch()?;
+
+            let Some(last_id) = page.last().map(|data_key| data_key.id) else {
+                return Ok(None);
+            };
+            Ok::<_, error_stack::Report<errors::DatabaseError>>(Some((
+                stream::iter(page.into_iter().map(Ok)),
+                last_id,
+            )))
+        });
+
+        Ok(pages.try_flatten().boxed())
+    }
+}
+
+async fn select_key(
+    connection: &mut AsyncPgConnection,
+    v: Version,
+    identifier: &Identifier,
+) -> CustomResult<DataKey, errors::DatabaseError> {
+    let (d_id, k_id) = identifier.get_identifier();
+
+    let query = DataKey::table().filter(
+        version
+            .eq(v)
+            .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+    );
+    query.get_result(connection).await.switch()
+}
+
+async fn select_keys(
+    connection: &mut AsyncPgConnection,
+    identifier: &Identifier,
+    versions: &[Version],
+) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+    let (d_id, k_id) = identifier.get_identifier();
+
+    let query = DataKey::table().filter(
+        version
+            .eq_any(versions.to_vec())
+            .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+    );
+    query.load(connection).await.switch()
+}
+
+/// Takes the advisory lock of the identifier until the end of the transaction, so that its
+/// rotations and rollbacks wait for each other
+async fn lock_identifier(
+    connection: &mut AsyncPgConnection,
+    identifier: &Identifier,
+) -> CustomResult<(), errors::DatabaseError> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind::<diesel::sql_types::Text, _>(identifier.to_string())
+        .execute(connection)
+        .await
+        .switch()?;
+
+    Ok(())
+}
+
+async fn select_newest_version(
+    connection: &mut AsyncPgConnection,
+    identifier: &Identifier,
+) -> CustomResult<Version, errors::DatabaseError> {
+    let (d_id, k_id) = identifier.get_identifier();
+    DataKey::table()
+        .select(version)
+        .order_by(version.desc())
+        .filter(data_identifier.eq(d_id).and(key_identifier.eq(k_id)))
+        .get_result(connection)
+        .await
+        .switch()
+}
+
+/// Rotates within a transaction that holds the advisory lock of the identifier, so that
+/// concurrent rotations of it wait for each other
+async fn rotate_locked(
+    connection: &mut AsyncPgConnection,
+    identifier: &Identifier,
+    newest: Version,
+    new: DataKeyNew,
+) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+    lock_identifier(connection, identifier).await?;
+
+    let (d_id, k_id) = identifier.get_identifier();
+    let current = select_newest_version(connection, identifier).await?;
+    if current != newest {
+        let data_key = DataKey::table()
+            .filter(
+                version
+                    .eq(current)
+                    .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+            )
+            .get_result(connection)
+            .await
+            .switch()?;
+        return Ok((data_key, false));
+    }
+
+    let data_key = diesel::insert_into(DataKey::table())
+        .values(new)
+        .get_result(connection)
+        .await
+        .switch()?;
+    Ok((data_key, true))
+}
+
+/// Rolls back within a transaction that holds the advisory lock of the identifier, the one its
+/// rotations take
+async fn rollback_locked(
+    connection: &mut AsyncPgConnection,
+    identifier: &Identifier,
+    newest: Version,
+    latest: Version,
+) -> CustomResult<(), errors::DatabaseError> {
+    lock_identifier(connection, identifier).await?;
+
+    let current = select_newest_version(connection, identifier).await?;
+    error_stack::ensure!(current == newest, errors::DatabaseError::UniqueViolation);
+
+    let (d_id, k_id) = identifier.get_identifier();
+    let deactivated = diesel::update(DataKey::table())
+        .filter(
+            version
+                .eq(latest)
+                .and(data_identifier.eq(d_id.clone()))
+                .and(key_identifier.eq(k_id.clone()))
+                .and(is_active.eq(true)),
+        )
+        .set(is_active.eq(false))
+        .execute(connection)
+        .await
+        .switch()?;
+    error_stack::ensure!(deactivated == 1, errors::DatabaseError::UniqueViolation);
+
+    let now = OffsetDateTime::now_utc();
+    diesel::insert_into(data_key_rollbacks::table)
+        .values((
+            data_key_rollbacks::key_identifier.eq(k_id),
+            data_key_rollbacks::data_identifier.eq(d_id),
+            data_key_rollbacks::version.eq(latest),
+            data_key_rollbacks::rolled_back_at.eq(PrimitiveDateTime::new(now.date(), now.time())),
+        ))
+        .execute(connection)
+        .await
+        .switch()?;
+
+    Ok(())
 }
@@ -0,0 +1,246 @@
+use diesel::{
+    QueryableByName,
+    sql_types::{BigInt, Text},
+};
+use diesel_async::{
+    AnsiTransactionManager, AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection,
+    TransactionManager,
+};
+use rustc_hash::FxHashSet;
+
+use crate::{
+    env::observability as logger,
+    errors::{self, CustomResult, SwitchError},
+};
+
+/// Migrations of the `migrations/` directory in order, by the version diesel records them with.
+/// The ones in [`EXCLUDED`] are left out.
+const MIGRATIONS: [(&str, &str); 10] = [
+    (
+        "20240528075150",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2024-05-28-075150_create_dek_table/up.sql"
+        )),
+    ),
+    (
+        "20240605101923",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2024-06-05-101923_create_created_at_index/up.sql"
+        )),
+    ),
+    (
+        "20240607102339",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2024-06-07-102339_version_to_int/up.sql"
+        )),
+    ),
+    (
+        "20240610081622",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2024-06-10-081622_add_source/up.sql"
+        )),
+    ),
+    (
+        "20240930125631",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2024-09-30-125631_add-token-column/up.sql"
+        )),
+    ),
+    (
+        "20261019083012",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2026-10-19-083012_add-key-metadata-column/up.sql"
+        )),
+    ),
+    (
+        "20261019101544",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2026-10-19-101544_add-kcv-column/up.sql"
+        )),
+    ),
+    (
+        "20261019131205",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2026-10-19-131205_add-parent-key-columns/up.sql"
+        )),
+    ),
+    (
+        "20261019154210",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2026-10-19-154210_add-is-active-column/up.sql"
+        )),
+    ),
+    (
+        "20261020091530",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/migrations/2026-10-20-091530_create-rollbacks-table/up.sql"
+        )),
+    ),
+];
+
+/// Migrations that are never applied at startup. Dropping the token column breaks a rollback to
+/// an older release, so it is left to an operator running the diesel CLI.
+const EXCLUDED: [&str; 1] = ["20260702130452"];
+
+/// Key of the session level advisory lock that serializes the migrations of concurrently
+/// starting instances
+const MIGRATION_LOCK_KEY: i64 = 0x6372_6970_7461;
+
+const CREATE_MIGRATIONS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS __diesel_schema_migrations (version VARCHAR(50) PRIMARY KEY NOT NULL, run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)";
+
+#[derive(QueryableByName)]
+struct AppliedMigration {
+    #[diesel(sql_type = Text)]
+    version: String,
+}
+
+/// Creates the schema if it is missing and applies the migrations it has not recorded, in the
+/// same `__diesel_schema_migrations` table as the diesel CLI. In a dry run the pending migrations
+/// are only logged.
+pub(super) async fn run(
+    connection: &mut AsyncPgConnection,
+    schema: &str,
+    dry_run: bool,
+) -> CustomResult<(), errors::DatabaseError> {
+    // Schemas can not be bound, so the name is checked before it is formatted into the query
+    error_stack::ensure!(
+        !schema.is_empty()
+            && schema
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_'),
+        errors::DatabaseError::InvalidValue
+    );
+
+    if dry_run {
+        return apply(connection, schema, dry_run).await;
+    }
+
+    // Held until the pending migrations are applied, another instance starting at the same time
+    // then reads them as applied once it acquires the lock
+    diesel::sql_query("SELECT pg_advisory_lock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+        .execute(connection)
+        .await
+        .switch()?;
+    let migrated = apply(connection, schema, dry_run).await;
+    diesel::sql_query("SELECT pg_advisory_unlock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+        .execute(connection)
+        .await
+        .switch()?;
+
+    migrated
+}
+
+async fn apply(
+    connection: &mut AsyncPgConnection,
+    schema: &str,
+    dry_run: bool,
+) -> CustomResult<(), errors::DatabaseError> {
+    if !dry_run {
+        connection
+            .batch_execute(&format!(
+                "CREATE SCHEMA IF NOT EXISTS {schema}; {CREATE_MIGRATIONS_TABLE_QUERY}"
+            ))
+            .await
+            .switch()?;
+    }
+
+    let applied = match diesel::sql_query(format!(
+        "SELECT version FROM {schema}.__diesel_schema_migrations"
+    ))
+    .load::<AppliedMigration>(connection)
+    .await
+    {
+        Ok(applied) => applied
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect::<FxHashSet<_>>(),
+        // A schema that was never bootstrapped has every migration pending
+        Err(diesel::result::Error::DatabaseError(..)) if dry_run => FxHashSet::default(),
+        Err(err) => return Err(err).switch(),
+    };
+
+    for (version, up) in MIGRATIONS {
+        if applied.contains(version) {
+            continue;
+        }
+        if dry_run {
+            logger::info!(schema, migration = version, "Pending postgres migration");
+            continue;
+        }
+
+        AnsiTransactionManager::begin_transaction(connection)
+            .await
+            .switch()?;
+        let migrated = async {
+            connection.batch_execute(up).await?;
+            diesel::sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES ($1)")
+                .bind::<Text, _>(version)
+                .execute(connection)
+                .await
+        }
+        .await;
+        match migrated {
+            Ok(_) => AnsiTransactionManager::commit_transaction(connection)
+                .await
+                .switch()?,
+            Err(_) => AnsiTransactionManager::rollback_transaction(connection)
+                .await
+                .switch()?,
+        }
+        migrated.switch()?;
+        logger::info!(schema, migration = version, "Applied postgres migration");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::expect_used)]
+    #[test]
+    fn test_every_migration_is_embedded() {
+        let mut directories = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations"))
+            .expect("Failed to read the migrations directory")
+            .map(|entry| {
+                let name = entry.expect("Failed to read a migration").file_name();
+                let name = name.to_string_lossy();
+                name.split('_')
+                    .next()
+                    .map(|version| version.replace('-', ""))
+                    .unwrap_or_default()
+            })
+            .filter(|version| !EXCLUDED.contains(&version.as_str()))
+            .collect::<Vec<_>>();
+        directories.sort();
+
+        let embedded = MIGRATIONS
+            .iter()
+            .map(|(version, _)| version.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(directories, embedded);
+    }
+
+    #[test]
+    fn test_no_production_unsafe_migration_is_embedded() {
+        for (version, up) in MIGRATIONS {
+            assert!(
+                !up.contains("Do not run this migration in production"),
+                "Migration {version} must not run at startup"
+            );
+        }
+    }
+}
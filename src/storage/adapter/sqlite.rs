@@ -0,0 +1,182 @@
+mod dek;
+mod migrations;
+
+use std::path::Path;
+
+use diesel::{
+    QueryableByName,
+    connection::{AnsiTransactionManager, TransactionManager},
+    sql_types::Text,
+};
+use diesel_async::{
+    AsyncConnection, RunQueryDsl, SimpleAsyncConnection,
+    pooled_connection::{AsyncDieselConnectionManager, ManagerConfig, bb8::Pool},
+    sync_connection_wrapper::SyncConnectionWrapper,
+};
+use error_stack::ResultExt;
+
+use crate::storage::{Config, DbState, adapter::Sqlite, errors};
+
+pub type SqliteConnection = SyncConnectionWrapper<diesel::SqliteConnection>;
+
+#[derive(QueryableByName)]
+struct JournalMode {
+    #[diesel(sql_type = Text)]
+    journal_mode: String,
+}
+
+#[async_trait::async_trait]
+impl super::DbAdapter for DbState<Pool<SqliteConnection>, Sqlite> {
+    type Conn<'a> = diesel_async::pooled_connection::bb8::PooledConnection<'a, SqliteConnection>;
+    type AdapterType = Sqlite;
+    type Pool = Pool<SqliteConnection>;
+
+    /// # Panics
+    ///
+    /// Panics if the database file is accessible by other users or unable to open it
+    #[allow(clippy::expect_used)]
+    async fn from_config(config: &Config, schema: &str) -> Self {
+        let sqlite = &config.sqlite;
+        let file = sqlite.directory.join(format!("{schema}.db"));
+        prepare_file(&sqlite.directory, &file).expect("Failed to prepare the sqlite database file");
+        let database_url = file
+            .to_str()
+            .expect("The sqlite database path is not valid UTF-8")
+            .to_string();
+
+        // Set on every connection, as SQLite keeps it per connection
+        let busy_timeout = sqlite.busy_timeout;
+        let mut mgr_config = ManagerConfig::default();
+        mgr_config.custom_setup = Box::new(move |url: &str| {
+            let url = url.to_string();
+            Box::pin(async move {
+                let mut connection = SqliteConnection::establish(&url).await?;
+                connection
+                    .batch_execute(&format!("PRAGMA busy_timeout = {busy_timeout}"))
+                    .await
+                    .map_err(diesel::ConnectionError::CouldntSetupConfiguration)?;
+                Ok(connection)
+            })
+        });
+
+        let mgr = AsyncDieselConnectionManager::<SqliteConnection>::new_with_config(
+            database_url.clone(),
+            mgr_config,
+        );
+        let pool = Pool::builder()
+            .max_size(sqlite.pool_size.unwrap_or(4))
+            .build(mgr)
+            .await
+            .expect("Failed to open the sqlite database");
+
+        let mut connection = pool
+            .get()
+            .await
+            .expect("Failed to get a connection to the sqlite database");
+        // The journal mode is stored in the file, readers then do not block the writer. The
+        // default `synchronous = FULL` is kept, a key lost on power failure can not be recovered.
+        let journal_mode = diesel::sql_query("PRAGMA journal_mode = WAL")
+            .get_result::<JournalMode>(&mut connection)
+            .await
+            .expect("Failed to enable the write ahead log");
+        assert!(
+            journal_mode.journal_mode.eq_ignore_ascii_case("wal"),
+            "The sqlite database {database_url} does not support the write ahead log"
+        );
+
+        if sqlite.migrations.run_on_startup {
+            migrations::run(&mut connection, &database_url, sqlite.migrations.dry_run)
+                .await
+                .expect("Failed to migrate the sqlite database");
+        }
+        drop(connection);
+
+        Self {
+            _adapter: std::marker::PhantomData,
+            pool,
+        }
+    }
+
+    async fn get_conn<'a>(
+        &'a self,
+    ) -> errors::CustomResult<Self::Conn<'a>, errors::ConnectionError> {
+        self.pool
+            .get()
+            .await
+            .change_context(errors::ConnectionError::ConnectionEstablishFailed)
+    }
+}
+
+/// Starts a transaction that takes the write lock of the database file up front. It goes through
+/// the transaction manager, so a connection returned to the pool before the transaction ends is
+/// discarded instead of reused with the lock held.
+async fn begin_immediate(connection: &mut SqliteConnection) -> diesel::QueryResult<()> {
+    connection
+        .spawn_blocking(|connection| {
+            AnsiTransactionManager::begin_transaction_sql(connection, "BEGIN IMMEDIATE")
+        })
+        .await
+}
+
+/// Commits or rolls back the transaction started by [`begin_immediate`]
+async fn end_transaction(
+    connection: &mut SqliteConnection,
+    commit: bool,
+) -> diesel::QueryResult<()> {
+    connection
+        .spawn_blocking(move |connection| {
+            if commit {
+                AnsiTransactionManager::commit_transaction(connection)
+            } else {
+                AnsiTransactionManager::rollback_transaction(connection)
+            }
+        })
+        .await
+}
+
+/// Creates the directory and the database file readable by the service user only, and refuses
+/// existing ones other users have access to. The write ahead log files SQLite creates next to
+/// the database take over its permissions.
+#[cfg(unix)]
+fn prepare_file(directory: &Path, file: &Path) -> errors::CustomResult<(), errors::DatabaseError> {
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(directory)
+        .change_context(errors::DatabaseError::Others)
+        .attach("Failed to create the sqlite directory")?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(file)
+        .change_context(errors::DatabaseError::Others)
+        .attach("Failed to create the sqlite database file")?;
+
+    for path in [directory, file] {
+        let mode = std::fs::metadata(path)
+            .change_context(errors::DatabaseError::Others)?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            return Err(
+                error_stack::Report::new(errors::DatabaseError::InvalidValue).attach(format!(
+                    "{} is accessible by other users, restrict its mode to the owner",
+                    path.display()
+                )),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn prepare_file(directory: &Path, _file: &Path) -> errors::CustomResult<(), errors::DatabaseError> {
+    std::fs::create_dir_all(directory)
+        .change_context(errors::DatabaseError::Others)
+        .attach("Failed to create the sqlite directory")
+}
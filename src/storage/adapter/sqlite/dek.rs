@@ -0,0 +1,368 @@
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, associations::HasTable};
+use diesel_async::{RunQueryDsl, pooled_connection::bb8::Pool};
+use error_stack::{IntoReport, ResultExt};
+use futures::{
+    StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use super::{DbState, SqliteConnection};
+use crate::{
+    errors::{self, CustomResult, SwitchError},
+    schema::{data_key_rollbacks, data_key_store::*},
+    storage::{
+        adapter::Sqlite,
+        dek::DataKeyStorageInterface,
+        types::{DataKey, DataKeyNew},
+    },
+    types::{Identifier, key::Version},
+};
+
+/// Rows read at a time when streaming the whole table
+const STREAM_PAGE_SIZE: i64 = 1000;
+
+#[async_trait::async_trait]
+impl DataKeyStorageInterface for DbState<Pool<SqliteConnection>, Sqlite> {
+    async fn get_or_insert_data_key(
+        &self,
+        new: DataKeyNew,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        let identifier: errors::CustomResult<Identifier, errors::ParsingError> =
+            (new.data_identifier.clone(), new.key_identifier.clone()).try_into();
+
+        let v = new.version;
+
+        let mut connection = self.get_conn().await.switch()?;
+        let query = diesel::insert_into(DataKey::table()).values(new);
+
+        match query.get_result(&mut connection).await.switch() {
+            Ok(result) => Ok(result),
+            Err(err) => match err.current_context() {
+                errors::DatabaseError::UniqueViolation => {
+                    self.get_key(
+                        v,
+                        &identifier
+                            .change_context(errors::DatabaseError::Others)
+                            .attach("Failed to parse identifier")?,
+                    )
+                    .await
+                }
+                _ => Err(err),
+            },
+        }
+    }
+
+    async fn get_latest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = DataKey::table()
+            .select(version)
+            .order_by(version.desc())
+            .filter(
+                data_identifier
+                    .eq(d_id)
+                    .and(key_identifier.eq(k_id))
+                    .and(is_active.eq(true)),
+            );
+
+        query.get_result(&mut connection).await.switch()
+    }
+
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = DataKey::table()
+            .select(version)
+            .order_by(version.desc())
+            .filter(
+                data_identifier
+                    .eq(d_id)
+                    .and(key_identifier.eq(k_id))
+                    .and(is_active.eq(true)),
+            );
+
+        query.load(&mut connection).await.switch()
+    }
+
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = DataKey::table()
+            .select(version)
+            .order_by(version.desc())
+            .filter(data_identifier.eq(d_id).and(key_identifier.eq(k_id)));
+
+        query.get_result(&mut connection).await.switch()
+    }
+
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+        let query = diesel::update(DataKey::table())
+            .filter(
+                version
+                    .eq(v)
+                    .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+            )
+            .set(is_active.eq(false));
+
+        match query.execute(&mut connection).await.switch()? {
+            0 => Err(errors::DatabaseError::NotFound.into_report()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn get_key(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+
+        let query = DataKey::table().filter(
+            version
+                .eq(v)
+                .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+        );
+        query.get_result(&mut connection).await.switch()
+    }
+
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = identifier.get_identifier();
+
+        let query = DataKey::table().filter(
+            version
+                .eq_any(versions.to_vec())
+                .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+        );
+        query.load(&mut connection).await.switch()
+    }
+
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+        let connection: &mut SqliteConnection = &mut connection;
+
+        // Takes the write lock of the database file up front, so that concurrent rotations wait
+        // for each other instead of failing to upgrade a read lock
+        super::begin_immediate(connection).await.switch()?;
+        let rotated = rotate_locked(connection, identifier, newest, new).await;
+        super::end_transaction(connection, rotated.is_ok())
+            .await
+            .switch()?;
+
+        rotated
+    }
+
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+        let connection: &mut SqliteConnection = &mut connection;
+
+        super::begin_immediate(connection).await.switch()?;
+        let rolled_back = rollback_locked(connection, identifier, newest, latest).await;
+        super::end_transaction(connection, rolled_back.is_ok())
+            .await
+            .switch()?;
+
+        rolled_back
+    }
+
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let rolled_back: Vec<(String, String)> = data_key_rollbacks::table
+            .select((
+                data_key_rollbacks::data_identifier,
+                data_key_rollbacks::key_identifier,
+            ))
+            .filter(data_key_rollbacks::rolled_back_at.ge(since))
+            .distinct()
+            .load(&mut connection)
+            .await
+            .switch()?;
+
+        rolled_back
+            .into_iter()
+            .map(|identifier| {
+                Identifier::try_from(identifier).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        let mut connection = self.get_conn().await.switch()?;
+
+        let (d_id, k_id) = parent.get_identifier();
+
+        let query = DataKey::table()
+            .select((data_identifier, key_identifier))
+            .filter(
+                parent_data_identifier
+                    .eq(d_id)
+                    .and(parent_key_identifier.eq(k_id)),
+            )
+            .distinct();
+        let children: Vec<(String, String)> = query.load(&mut connection).await.switch()?;
+
+        children
+            .into_iter()
+            .map(|child| {
+                Identifier::try_from(child).change_context(errors::DatabaseError::InvalidValue)
+            })
+            .collect()
+    }
+
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    > {
+        // Pages by primary key, so that rows inserted while streaming do not shift the pages
+        let pages = stream::try_unfold(0, move |last_id| async move {
+            let mut connection = self.get_conn().await.switch()?;
+            let page: Vec<DataKey> = DataKey::table()
+                .filter(id.gt(last_id))
+                .order_by(id.asc())
+                .limit(STREAM_PAGE_SIZE)
+                .load(&mut connection)
+                .await
+                .switch()?;
+
+            let Some(last_id) = page.last().map(|data_key| data_key.id) else {
+                return Ok(None);
+            };
+            Ok::<_, error_stack::Report<errors::DatabaseError>>(Some((
+                stream::iter(page.into_iter().map(Ok)),
+                last_id,
+            )))
+        });
+
+        Ok(pages.try_flatten().boxed())
+    }
+}
+
+async fn select_newest_version(
+    connection: &mut SqliteConnection,
+    identifier: &Identifier,
+) -> CustomResult<Version, errors::DatabaseError> {
+    let (d_id, k_id) = identifier.get_identifier();
+    DataKey::table()
+        .select(version)
+        .order_by(version.desc())
+        .filter(data_identifier.eq(d_id).and(key_identifier.eq(k_id)))
+        .get_result(connection)
+        .await
+        .switch()
+}
+
+/// Rotates within a transaction that holds the write lock of the database
+async fn rotate_locked(
+    connection: &mut SqliteConnection,
+    identifier: &Identifier,
+    newest: Version,
+    new: DataKeyNew,
+) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+    let (d_id, k_id) = identifier.get_identifier();
+    let current = select_newest_version(connection, identifier).await?;
+    if current != newest {
+        let data_key = DataKey::table()
+            .filter(
+                version
+                    .eq(current)
+                    .and(data_identifier.eq(d_id).and(key_identifier.eq(k_id))),
+            )
+            .get_result(connection)
+            .await
+            .switch()?;
+        return Ok((data_key, false));
+    }
+
+    let data_key = diesel::insert_into(DataKey::table())
+        .values(new)
+        .get_result(connection)
+        .await
+        .switch()?;
+    Ok((data_key, true))
+}
+
+/// Rolls back within a transaction that holds the write lock of the database, like rotations
+async fn rollback_locked(
+    connection: &mut SqliteConnection,
+    identifier: &Identifier,
+    newest: Version,
+    latest: Version,
+) -> CustomResult<(), errors::DatabaseError> {
+    let current = select_newest_version(connection, identifier).await?;
+    error_stack::ensure!(current == newest, errors::DatabaseError::UniqueViolation);
+
+    let (d_id, k_id) = identifier.get_identifier();
+    let deactivated = diesel::update(DataKey::table())
+        .filter(
+            version
+                .eq(latest)
+                .and(data_identifier.eq(d_id.clone()))
+                .and(key_identifier.eq(k_id.clone()))
+                .and(is_active.eq(true)),
+        )
+        .set(is_active.eq(false))
+        .execute(connection)
+        .await
+        .switch()?;
+    error_stack::ensure!(deactivated == 1, errors::DatabaseError::UniqueViolation);
+
+    let now = OffsetDateTime::now_utc();
+    diesel::insert_into(data_key_rollbacks::table)
+        .values((
+            data_key_rollbacks::key_identifier.eq(k_id),
+            data_key_rollbacks::data_identifier.eq(d_id),
+            data_key_rollbacks::version.eq(latest),
+            data_key_rollbacks::rolled_back_at.eq(PrimitiveDateTime::new(now.date(), now.time())),
+        ))
+        .execute(connection)
+        .await
+        .switch()?;
+
+    Ok(())
+}
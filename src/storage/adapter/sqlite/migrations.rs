@@ -0,0 +1,209 @@
+use diesel::{QueryableByName, sql_types::Text};
+use diesel_async::{RunQueryDsl, SimpleAsyncConnection};
+use rustc_hash::FxHashSet;
+
+use super::SqliteConnection;
+use crate::{
+    env::observability as logger,
+    errors::{self, CustomResult, SwitchError},
+};
+
+/// Migrations of the `sqlite/migrations/` directory in order. They mirror the Postgres
+/// `migrations/` one by one and are recorded with the same versions. The ones in [`EXCLUDED`] are
+/// left out.
+const MIGRATIONS: [(&str, &str); 10] = [
+    (
+        "20240528075150",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2024-05-28-075150_create_dek_table/up.sql"
+        )),
+    ),
+    (
+        "20240605101923",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2024-06-05-101923_create_created_at_index/up.sql"
+        )),
+    ),
+    (
+        "20240607102339",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2024-06-07-102339_version_to_int/up.sql"
+        )),
+    ),
+    (
+        "20240610081622",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2024-06-10-081622_add_source/up.sql"
+        )),
+    ),
+    (
+        "20240930125631",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2024-09-30-125631_add-token-column/up.sql"
+        )),
+    ),
+    (
+        "20261019083012",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2026-10-19-083012_add-key-metadata-column/up.sql"
+        )),
+    ),
+    (
+        "20261019101544",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2026-10-19-101544_add-kcv-column/up.sql"
+        )),
+    ),
+    (
+        "20261019131205",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2026-10-19-131205_add-parent-key-columns/up.sql"
+        )),
+    ),
+    (
+        "20261019154210",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2026-10-19-154210_add-is-active-column/up.sql"
+        )),
+    ),
+    (
+        "20261020091530",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/sqlite/migrations/2026-10-20-091530_create-rollbacks-table/up.sql"
+        )),
+    ),
+];
+
+/// Migrations that are never applied at startup, as for Postgres
+const EXCLUDED: [&str; 1] = ["20260702130452"];
+
+const CREATE_MIGRATIONS_TABLE_QUERY: &str = "CREATE TABLE IF NOT EXISTS __diesel_schema_migrations (version VARCHAR(50) PRIMARY KEY NOT NULL, run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)";
+
+#[derive(QueryableByName)]
+struct AppliedMigration {
+    #[diesel(sql_type = Text)]
+    version: String,
+}
+
+/// Applies the migrations the database file has not recorded, in the same
+/// `__diesel_schema_migrations` table as the diesel CLI. In a dry run the pending migrations are
+/// only logged.
+pub(super) async fn run(
+    connection: &mut SqliteConnection,
+    file: &str,
+    dry_run: bool,
+) -> CustomResult<(), errors::DatabaseError> {
+    if !dry_run {
+        connection
+            .batch_execute(CREATE_MIGRATIONS_TABLE_QUERY)
+            .await
+            .switch()?;
+    }
+
+    let applied = match diesel::sql_query("SELECT version FROM __diesel_schema_migrations")
+        .load::<AppliedMigration>(connection)
+        .await
+    {
+        Ok(applied) => applied
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect::<FxHashSet<_>>(),
+        // A database that was never bootstrapped has every migration pending
+        Err(diesel::result::Error::DatabaseError(..)) if dry_run => FxHashSet::default(),
+        Err(err) => return Err(err).switch(),
+    };
+
+    for (version, up) in MIGRATIONS {
+        if applied.contains(version) {
+            continue;
+        }
+        if dry_run {
+            logger::info!(file, migration = version, "Pending sqlite migration");
+            continue;
+        }
+
+        super::begin_immediate(connection).await.switch()?;
+        let migrated = async {
+            connection.batch_execute(up).await?;
+            diesel::sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES (?)")
+                .bind::<Text, _>(version)
+                .execute(connection)
+                .await
+        }
+        .await;
+        super::end_transaction(connection, migrated.is_ok())
+            .await
+            .switch()?;
+        migrated.switch()?;
+        logger::info!(file, migration = version, "Applied sqlite migration");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::expect_used)]
+    fn migration_directories(path: &str) -> Vec<String> {
+        let mut directories = std::fs::read_dir(path)
+            .expect("Failed to read the migrations directory")
+            .map(|entry| {
+                entry
+                    .expect("Failed to read a migration")
+                    .file_name()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        directories.sort();
+        directories
+    }
+
+    #[test]
+    fn test_migrations_mirror_postgres() {
+        let directories =
+            migration_directories(concat!(env!("CARGO_MANIFEST_DIR"), "/sqlite/migrations"));
+        assert_eq!(
+            directories,
+            migration_directories(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations"))
+        );
+
+        let embedded = MIGRATIONS
+            .iter()
+            .map(|(version, _)| version.to_string())
+            .collect::<Vec<_>>();
+        let versions = directories
+            .iter()
+            .map(|name| {
+                name.split('_')
+                    .next()
+                    .map(|version| version.replace('-', ""))
+                    .unwrap_or_default()
+            })
+            .filter(|version| !EXCLUDED.contains(&version.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(versions, embedded);
+    }
+
+    #[test]
+    fn test_no_production_unsafe_migration_is_embedded() {
+        for (version, up) in MIGRATIONS {
+            assert!(
+                !up.contains("Do not run this migration in production"),
+                "Migration {version} must not run at startup"
+            );
+        }
+    }
+}
@@ -36,7 +36,7 @@ where
 {
     fn new(time_to_live: u64, time_to_idle: u64, max_capacity: Option<u64>) -> Self {
         let mut cache_builder = MokaCache::builder()
-            .time_to_idle(std::time::Duration::from_secs(time_to_live))
+            .time_to_live(std::time::Duration::from_secs(time_to_live))
             .time_to_idle(std::time::Duration::from_secs(time_to_idle));
 
         if let Some(capacity) = max_capacity {
@@ -55,4 +55,8 @@ where
     pub async fn get(&self, key: &Key) -> Option<V> {
         self.inner.get(key).await
     }
+
+    pub async fn remove(&self, key: &Key) {
+        self.inner.invalidate(key).await;
+    }
 }
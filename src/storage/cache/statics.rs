@@ -1,10 +1,20 @@
+use std::time::Duration;
+
 use once_cell::sync::Lazy;
 
 use super::Cache;
 use crate::types::{Key, key::Version};
 
-const TIME_TO_LIVE: u64 = 30;
+/// Seconds an entry is cached for at most, which bounds how long other instances serve a
+/// version or key changed by this one
+pub const TIME_TO_LIVE: u64 = 30;
 const TIME_TO_IDLE: u64 = 30;
+
+/// Interval at which every instance polls the rollbacks of its tenants
+pub const ROLLBACK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Rollbacks are polled again for this long, which covers clock skew between instances and
+/// lookups that cached the rolled back version while it was being invalidated
+pub const ROLLBACK_POLL_OVERLAP: Duration = Duration::from_secs(10);
 const SIZE: u64 = 30;
 
 pub static VERSION_CACHE: Lazy<Cache<Version>> =
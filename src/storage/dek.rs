@@ -1,6 +1,12 @@
+use futures::stream::BoxStream;
+use time::PrimitiveDateTime;
+
 use crate::{
     errors::{self, CustomResult},
-    storage::types::{DataKey, DataKeyNew},
+    storage::{
+        StorageState,
+        types::{DataKey, DataKeyNew},
+    },
     types::{Identifier, key::Version},
 };
 
@@ -10,13 +16,240 @@ pub trait DataKeyStorageInterface {
         &self,
         new: DataKeyNew,
     ) -> CustomResult<DataKey, errors::DatabaseError>;
+    /// Latest active version, which is the one data is encrypted with
     async fn get_latest_version(
         &self,
         identifier: &Identifier,
     ) -> CustomResult<Version, errors::DatabaseError>;
+    /// Active versions, newest first
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError>;
+    /// Newest version including rolled back ones, new versions are created after it
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError>;
+    /// Stops encrypting with `v`, data encrypted with it can still be decrypted
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError>;
     async fn get_key(
         &self,
         v: Version,
         identifier: &Identifier,
     ) -> CustomResult<DataKey, errors::DatabaseError>;
+    /// The stored versions among `versions` in a single lookup, in no particular order
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError>;
+    /// Inserts `new` as the version after `newest`, serialized with other rotations of the
+    /// identifier. If another rotation created a newer version in the meantime, that version is
+    /// returned instead and `new` is discarded. The flag tells whether the returned version was
+    /// created by this call.
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError>;
+    /// Deactivates `latest`, serialized with rotations of the identifier, and records the
+    /// rollback for [`Self::get_rolled_back`]. Fails with a unique violation if a version after
+    /// `newest` was created or `latest` was deactivated in the meantime.
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError>;
+    /// Identifiers rolled back at or after `since`
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError>;
+    /// Identifiers with at least one key version wrapped by a key of `parent`
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError>;
+    /// Every stored key version, in no particular order
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    >;
+}
+
+#[async_trait::async_trait]
+impl DataKeyStorageInterface for StorageState {
+    async fn get_or_insert_data_key(
+        &self,
+        new: DataKeyNew,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_or_insert_data_key(new).await,
+            Self::Cassandra(db) => db.get_or_insert_data_key(new).await,
+            Self::Memory(db) => db.get_or_insert_data_key(new).await,
+            Self::Sqlite(db) => db.get_or_insert_data_key(new).await,
+            Self::DynamoDb(db) => db.get_or_insert_data_key(new).await,
+        }
+    }
+
+    async fn get_latest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_latest_version(identifier).await,
+            Self::Cassandra(db) => db.get_latest_version(identifier).await,
+            Self::Memory(db) => db.get_latest_version(identifier).await,
+            Self::Sqlite(db) => db.get_latest_version(identifier).await,
+            Self::DynamoDb(db) => db.get_latest_version(identifier).await,
+        }
+    }
+
+    async fn get_active_versions(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Vec<Version>, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_active_versions(identifier).await,
+            Self::Cassandra(db) => db.get_active_versions(identifier).await,
+            Self::Memory(db) => db.get_active_versions(identifier).await,
+            Self::Sqlite(db) => db.get_active_versions(identifier).await,
+            Self::DynamoDb(db) => db.get_active_versions(identifier).await,
+        }
+    }
+
+    async fn get_newest_version(
+        &self,
+        identifier: &Identifier,
+    ) -> CustomResult<Version, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_newest_version(identifier).await,
+            Self::Cassandra(db) => db.get_newest_version(identifier).await,
+            Self::Memory(db) => db.get_newest_version(identifier).await,
+            Self::Sqlite(db) => db.get_newest_version(identifier).await,
+            Self::DynamoDb(db) => db.get_newest_version(identifier).await,
+        }
+    }
+
+    async fn deactivate_version(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.deactivate_version(v, identifier).await,
+            Self::Cassandra(db) => db.deactivate_version(v, identifier).await,
+            Self::Memory(db) => db.deactivate_version(v, identifier).await,
+            Self::Sqlite(db) => db.deactivate_version(v, identifier).await,
+            Self::DynamoDb(db) => db.deactivate_version(v, identifier).await,
+        }
+    }
+
+    async fn get_key(
+        &self,
+        v: Version,
+        identifier: &Identifier,
+    ) -> CustomResult<DataKey, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_key(v, identifier).await,
+            Self::Cassandra(db) => db.get_key(v, identifier).await,
+            Self::Memory(db) => db.get_key(v, identifier).await,
+            Self::Sqlite(db) => db.get_key(v, identifier).await,
+            Self::DynamoDb(db) => db.get_key(v, identifier).await,
+        }
+    }
+
+    async fn get_keys(
+        &self,
+        identifier: &Identifier,
+        versions: &[Version],
+    ) -> CustomResult<Vec<DataKey>, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_keys(identifier, versions).await,
+            Self::Cassandra(db) => db.get_keys(identifier, versions).await,
+            Self::Memory(db) => db.get_keys(identifier, versions).await,
+            Self::Sqlite(db) => db.get_keys(identifier, versions).await,
+            Self::DynamoDb(db) => db.get_keys(identifier, versions).await,
+        }
+    }
+
+    async fn rotate_data_key(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        new: DataKeyNew,
+    ) -> CustomResult<(DataKey, bool), errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.rotate_data_key(identifier, newest, new).await,
+            Self::Cassandra(db) => db.rotate_data_key(identifier, newest, new).await,
+            Self::Memory(db) => db.rotate_data_key(identifier, newest, new).await,
+            Self::Sqlite(db) => db.rotate_data_key(identifier, newest, new).await,
+            Self::DynamoDb(db) => db.rotate_data_key(identifier, newest, new).await,
+        }
+    }
+
+    async fn rollback_version(
+        &self,
+        identifier: &Identifier,
+        newest: Version,
+        latest: Version,
+    ) -> CustomResult<(), errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.rollback_version(identifier, newest, latest).await,
+            Self::Cassandra(db) => db.rollback_version(identifier, newest, latest).await,
+            Self::Memory(db) => db.rollback_version(identifier, newest, latest).await,
+            Self::Sqlite(db) => db.rollback_version(identifier, newest, latest).await,
+            Self::DynamoDb(db) => db.rollback_version(identifier, newest, latest).await,
+        }
+    }
+
+    async fn get_rolled_back(
+        &self,
+        since: PrimitiveDateTime,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_rolled_back(since).await,
+            Self::Cassandra(db) => db.get_rolled_back(since).await,
+            Self::Memory(db) => db.get_rolled_back(since).await,
+            Self::Sqlite(db) => db.get_rolled_back(since).await,
+            Self::DynamoDb(db) => db.get_rolled_back(since).await,
+        }
+    }
+
+    async fn get_children(
+        &self,
+        parent: &Identifier,
+    ) -> CustomResult<Vec<Identifier>, errors::DatabaseError> {
+        match self {
+            Self::Postgres(db) => db.get_children(parent).await,
+            Self::Cassandra(db) => db.get_children(parent).await,
+            Self::Memory(db) => db.get_children(parent).await,
+            Self::Sqlite(db) => db.get_children(parent).await,
+            Self::DynamoDb(db) => db.get_children(parent).await,
+        }
+    }
+
+    async fn stream_keys(
+        &self,
+    ) -> CustomResult<
+        BoxStream<'_, CustomResult<DataKey, errors::DatabaseError>>,
+        errors::DatabaseError,
+    > {
+        match self {
+            Self::Postgres(db) => db.stream_keys().await,
+            Self::Cassandra(db) => db.stream_keys().await,
+            Self::Memory(db) => db.stream_keys().await,
+            Self::Sqlite(db) => db.stream_keys().await,
+            Self::DynamoDb(db) => db.stream_keys().await,
+        }
+    }
 }
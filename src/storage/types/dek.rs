@@ -3,7 +3,11 @@ use diesel::{Identifiable, Insertable, Queryable};
 use hyperswitch_masking::StrongSecret;
 use time::{OffsetDateTime, PrimitiveDateTime};
 
-use crate::{schema::data_key_store, types::key::Version};
+use crate::{
+    errors::{self, CustomResult},
+    schema::data_key_store,
+    types::key::{ParentKey, Version},
+};
 
 #[derive(Insertable)]
 #[diesel(table_name = data_key_store)]
@@ -14,9 +18,15 @@ pub struct DataKeyNew {
     pub version: Version,
     pub created_at: PrimitiveDateTime,
     pub source: String,
+    pub key_metadata: Option<String>,
+    pub kcv: Option<String>,
+    pub parent_key_identifier: Option<String>,
+    pub parent_data_identifier: Option<String>,
+    pub parent_version: Option<Version>,
+    pub is_active: bool,
 }
 
-#[derive(Queryable, Identifiable)]
+#[derive(Clone, Queryable, Identifiable)]
 #[diesel(table_name = data_key_store)]
 pub struct DataKey {
     pub id: i32,
@@ -26,6 +36,30 @@ pub struct DataKey {
     pub version: Version,
     pub created_at: PrimitiveDateTime,
     pub source: String,
+    pub key_metadata: Option<String>,
+    pub kcv: Option<String>,
+    pub parent_key_identifier: Option<String>,
+    pub parent_data_identifier: Option<String>,
+    pub parent_version: Option<Version>,
+    /// Inactive versions are not used for encryption, but still decrypt data encrypted with them
+    pub is_active: bool,
+}
+
+impl DataKey {
+    /// Parent key version the data key is wrapped with, `None` for keys wrapped by the KEK
+    pub fn parent(&self) -> CustomResult<Option<ParentKey>, errors::ParsingError> {
+        match (
+            &self.parent_data_identifier,
+            &self.parent_key_identifier,
+            self.parent_version,
+        ) {
+            (Some(data_identifier), Some(key_identifier), Some(version)) => Ok(Some(ParentKey {
+                identifier: (data_identifier.clone(), key_identifier.clone()).try_into()?,
+                version,
+            })),
+            _ => Ok(None),
+        }
+    }
 }
 
 // Cassandra representation of `DataKey`.
@@ -49,6 +83,13 @@ pub struct CassandraDataKey {
     pub version: Version,
     pub created_at: OffsetDateTime,
     pub source: String,
+    pub key_metadata: Option<String>,
+    pub kcv: Option<String>,
+    pub parent_key_identifier: Option<String>,
+    pub parent_data_identifier: Option<String>,
+    pub parent_version: Option<Version>,
+    /// Rows written before versions could be rolled back have no value and are active
+    pub is_active: Option<bool>,
 }
 
 impl From<CassandraDataKey> for DataKey {
@@ -62,6 +103,12 @@ impl From<CassandraDataKey> for DataKey {
             version: value.version,
             created_at: PrimitiveDateTime::new(utc_created_at.date(), utc_created_at.time()),
             source: value.source,
+            key_metadata: value.key_metadata,
+            kcv: value.kcv,
+            parent_key_identifier: value.parent_key_identifier,
+            parent_data_identifier: value.parent_data_identifier,
+            parent_version: value.parent_version,
+            is_active: value.is_active.unwrap_or(true),
         }
     }
 }
@@ -76,6 +123,12 @@ impl From<DataKey> for CassandraDataKey {
             version: value.version,
             created_at: value.created_at.assume_utc(),
             source: value.source,
+            key_metadata: value.key_metadata,
+            kcv: value.kcv,
+            parent_key_identifier: value.parent_key_identifier,
+            parent_data_identifier: value.parent_data_identifier,
+            parent_version: value.parent_version,
+            is_active: Some(value.is_active),
         }
     }
 }
@@ -90,6 +143,31 @@ impl From<DataKeyNew> for DataKey {
             version: value.version,
             created_at: value.created_at,
             source: value.source,
+            key_metadata: value.key_metadata,
+            kcv: value.kcv,
+            parent_key_identifier: value.parent_key_identifier,
+            parent_data_identifier: value.parent_data_identifier,
+            parent_version: value.parent_version,
+            is_active: value.is_active,
+        }
+    }
+}
+
+impl From<DataKey> for DataKeyNew {
+    fn from(value: DataKey) -> Self {
+        Self {
+            key_identifier: value.key_identifier,
+            data_identifier: value.data_identifier,
+            encryption_key: value.encryption_key,
+            version: value.version,
+            created_at: value.created_at,
+            source: value.source,
+            key_metadata: value.key_metadata,
+            kcv: value.kcv,
+            parent_key_identifier: value.parent_key_identifier,
+            parent_data_identifier: value.parent_data_identifier,
+            parent_version: value.parent_version,
+            is_active: value.is_active,
         }
     }
 }
@@ -8,7 +8,10 @@ use serde::{
     de::{self, Deserialize, Deserializer, Unexpected, Visitor},
 };
 
-use crate::{consts::base64::BASE64_ENGINE, types::key::Version};
+use crate::{
+    consts::base64::BASE64_ENGINE,
+    types::{Identifier, key::Version},
+};
 
 #[derive(Eq, PartialEq, Serialize, serde::Deserialize, Debug, Clone)]
 pub struct MultipleDecryptionDataGroup(pub Vec<DecryptedDataGroup>);
@@ -76,16 +79,62 @@ pub struct MultipleEncryptionDataGroup(pub Vec<EncryptedDataGroup>);
 #[derive(Eq, PartialEq, Serialize, serde::Deserialize, Debug, Clone)]
 pub struct EncryptedDataGroup(pub FxHashMap<String, EncryptedData>);
 
+/// Prefix of ciphertexts that carry their own wrapped data key
+const SELF_CONTAINED_PREFIX: &str = "kek";
+/// Prefix of ciphertexts that carry their content key wrapped for each identifier sharing them
+const SHARED_PREFIX: &str = "shared";
+
+/// Content key of a shared ciphertext wrapped by a data key version of one of its identifiers
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, serde::Deserialize)]
+pub struct SharedKey {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub version: Version,
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    pub wrapped_key: Vec<u8>,
+}
+
+fn serialize_base64<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&BASE64_ENGINE.encode(value))
+}
+
+fn deserialize_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    BASE64_ENGINE.decode(value).map_err(de::Error::custom)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct EncryptedData {
     pub version: Version,
     pub data: hyperswitch_masking::StrongSecret<Vec<u8>>,
+    /// Data key wrapped by the KMS for self-contained ciphertexts, `version` is unused when set
+    pub wrapped_key: Option<hyperswitch_masking::StrongSecret<Vec<u8>>>,
+    /// Purpose the data was encrypted for, the data key is derived into a sub-key when set
+    pub purpose: Option<String>,
+    /// Content key wrapped for every identifier the data is shared with, `version` is unused when
+    /// not empty
+    pub shared_keys: Vec<SharedKey>,
 }
 
 impl EncryptedData {
     pub fn inner(self) -> hyperswitch_masking::StrongSecret<Vec<u8>> {
         self.data
     }
+
+    /// Whether the data is encrypted with a key carried in the ciphertext rather than directly
+    /// with a data key version
+    pub fn has_embedded_key(&self) -> bool {
+        self.wrapped_key.is_some() || !self.shared_keys.is_empty()
+    }
 }
 impl Serialize for EncryptedData {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -93,7 +142,22 @@ impl Serialize for EncryptedData {
         S: serde::Serializer,
     {
         let data = BASE64_ENGINE.encode(self.data.peek());
-        let encoded = format!("{}:{}", self.version, data);
+        let encoded = match (&self.wrapped_key, &self.purpose) {
+            (Some(wrapped_key), _) => format!(
+                "{SELF_CONTAINED_PREFIX}:{}:{data}",
+                BASE64_ENGINE.encode(wrapped_key.peek())
+            ),
+            _ if !self.shared_keys.is_empty() => {
+                let shared_keys =
+                    serde_json::to_vec(&self.shared_keys).map_err(serde::ser::Error::custom)?;
+                format!(
+                    "{SHARED_PREFIX}:{}:{data}",
+                    BASE64_ENGINE.encode(shared_keys)
+                )
+            }
+            (None, Some(purpose)) => format!("{}:{purpose}:{data}", self.version),
+            (None, None) => format!("{}:{}", self.version, data),
+        };
         serializer.serialize_str(&encoded)
     }
 }
@@ -123,10 +187,61 @@ impl<'de> Deserialize<'de> for EncryptedData {
                     )
                 })?;
 
-                let dec_data = BASE64_ENGINE.decode(data).map_err(|err| {
-                    let err = err.to_string();
-                    E::invalid_value(Unexpected::Str(data), &err.as_str())
-                })?;
+                let decode = |data: &str| {
+                    BASE64_ENGINE.decode(data).map_err(|err| {
+                        let err = err.to_string();
+                        E::invalid_value(Unexpected::Str(data), &err.as_str())
+                    })
+                };
+
+                if version == SELF_CONTAINED_PREFIX {
+                    let (wrapped_key, data) = data.split_once(':').ok_or_else(|| {
+                        E::invalid_value(
+                            Unexpected::Str(value),
+                            &"String should of the format kek:{base64_wrapped_key}:{base64_encoded_data}",
+                        )
+                    })?;
+
+                    return Ok(EncryptedData {
+                        version: Version::default(),
+                        data: hyperswitch_masking::StrongSecret::new(decode(data)?),
+                        wrapped_key: Some(hyperswitch_masking::StrongSecret::new(decode(
+                            wrapped_key,
+                        )?)),
+                        purpose: None,
+                        shared_keys: Vec::new(),
+                    });
+                }
+
+                if version == SHARED_PREFIX {
+                    let (shared_keys, data) = data.split_once(':').ok_or_else(|| {
+                        E::invalid_value(
+                            Unexpected::Str(value),
+                            &"String should of the format shared:{base64_shared_keys}:{base64_encoded_data}",
+                        )
+                    })?;
+                    let shared_keys = serde_json::from_slice::<Vec<SharedKey>>(&decode(
+                        shared_keys,
+                    )?)
+                    .map_err(|err| {
+                        let err = err.to_string();
+                        E::invalid_value(Unexpected::Str(shared_keys), &err.as_str())
+                    })?;
+
+                    return Ok(EncryptedData {
+                        version: Version::default(),
+                        data: hyperswitch_masking::StrongSecret::new(decode(data)?),
+                        wrapped_key: None,
+                        purpose: None,
+                        shared_keys,
+                    });
+                }
+
+                let (purpose, data) = match data.split_once(':') {
+                    Some((purpose, data)) => (Some(purpose.to_string()), data),
+                    None => (None, data),
+                };
+                let dec_data = decode(data)?;
 
                 let (_, version) = version.split_once('v').ok_or_else(|| {
                     E::invalid_value(
@@ -142,6 +257,9 @@ impl<'de> Deserialize<'de> for EncryptedData {
                 Ok(EncryptedData {
                     version: Version::from(version),
                     data: hyperswitch_masking::StrongSecret::new(dec_data),
+                    wrapped_key: None,
+                    purpose,
+                    shared_keys: Vec::new(),
                 })
             }
         }
@@ -172,6 +290,9 @@ mod tests {
             data: hyperswitch_masking::StrongSecret::new(
                 String::from("Omgit'sworking").as_bytes().to_vec(),
             ),
+            wrapped_key: None,
+            purpose: None,
+            shared_keys: Vec::new(),
         };
 
         let expected_data = ExtractedEncryptedData {
@@ -179,4 +300,54 @@ mod tests {
         };
         assert_eq!(actual_data, expected_data);
     }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_self_contained_data_round_trip() {
+        let data = serde_json::json!("kek:d3JhcHBlZA==:T21naXQnc3dvcmtpbmc=");
+        let actual_data: EncryptedData = serde_json::from_value(data.clone()).unwrap();
+
+        assert_eq!(
+            actual_data
+                .wrapped_key
+                .as_ref()
+                .map(|key| key.peek().as_slice()),
+            Some(b"wrapped".as_slice())
+        );
+        assert_eq!(actual_data.data.peek().as_slice(), b"Omgit'sworking");
+        assert_eq!(serde_json::to_value(&actual_data).unwrap(), data);
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_purpose_data_round_trip() {
+        let data = serde_json::json!("v2:card:T21naXQnc3dvcmtpbmc=");
+        let actual_data: EncryptedData = serde_json::from_value(data.clone()).unwrap();
+
+        assert_eq!(actual_data.version, Version::from(2));
+        assert_eq!(actual_data.purpose.as_deref(), Some("card"));
+        assert_eq!(serde_json::to_value(&actual_data).unwrap(), data);
+    }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_shared_data_round_trip() {
+        let encrypted_data = EncryptedData {
+            version: Version::default(),
+            data: hyperswitch_masking::StrongSecret::new(b"payout".to_vec()),
+            wrapped_key: None,
+            purpose: None,
+            shared_keys: vec![SharedKey {
+                identifier: Identifier::new(String::from("Merchant"), String::from("sub_1")),
+                version: Version::from(2),
+                wrapped_key: b"wrapped".to_vec(),
+            }],
+        };
+
+        let serialized = serde_json::to_value(&encrypted_data).unwrap();
+        assert!(serialized.as_str().unwrap().starts_with("shared:"));
+
+        let actual_data: EncryptedData = serde_json::from_value(serialized).unwrap();
+        assert_eq!(actual_data, encrypted_data);
+    }
 }
@@ -2,55 +2,84 @@ use serde::{Deserialize, Serialize};
 
 use crate::errors;
 
+/// Length of `data_key_store.data_identifier`
+pub const MAX_DATA_IDENTIFIER_LEN: usize = 20;
+
+/// Data identifiers allowed for a tenant that does not configure its own
+pub const DEFAULT_DATA_IDENTIFIERS: [&str; 5] =
+    ["User", "Merchant", "UserAuth", "Entity", "ReconMerchant"];
+
+/// Owner of a data key. The `data_identifier` is one of the types configured for the tenant and
+/// the `key_identifier` is the id of the entity within it.
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
-#[serde(tag = "data_identifier", content = "key_identifier")]
-pub enum Identifier {
-    User(String),
-    Merchant(String),
-    UserAuth(String),
-    Entity(String),
-    ReconMerchant(String),
+pub struct Identifier {
+    data_identifier: String,
+    key_identifier: String,
 }
 
 impl Identifier {
-    pub fn get_identifier(&self) -> (String, String) {
-        match self {
-            Self::User(id) => (String::from("User"), id.clone()),
-            Self::Merchant(id) => (String::from("Merchant"), id.clone()),
-            Self::UserAuth(id) => (String::from("UserAuth"), id.clone()),
-            Self::Entity(id) => (String::from("Entity"), id.clone()),
-            Self::ReconMerchant(id) => (String::from("ReconMerchant"), id.clone()),
+    pub fn new(data_identifier: String, key_identifier: String) -> Self {
+        Self {
+            data_identifier,
+            key_identifier,
         }
     }
+
+    pub fn data_identifier(&self) -> &str {
+        &self.data_identifier
+    }
+
+    pub fn get_identifier(&self) -> (String, String) {
+        (self.data_identifier.clone(), self.key_identifier.clone())
+    }
+
+    /// Data identifiers are limited to ASCII alphanumerics, so the `_` separating them from the
+    /// key identifier in [`Identifier`]'s display form is unambiguous
+    pub fn is_valid_data_identifier(data_identifier: &str) -> bool {
+        !data_identifier.is_empty()
+            && data_identifier.len() <= MAX_DATA_IDENTIFIER_LEN
+            && data_identifier
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric())
+    }
 }
 
 impl core::fmt::Display for Identifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::User(s) => write!(f, "User_{s}"),
-            Self::Merchant(s) => write!(f, "Merchant_{s}"),
-            Self::UserAuth(s) => write!(f, "UserAuth_{s}"),
-            Self::Entity(s) => write!(f, "Entity_{s}"),
-            Self::ReconMerchant(s) => write!(f, "ReconMerchant_{s}"),
-        }
+        write!(f, "{}_{}", self.data_identifier, self.key_identifier)
     }
 }
 
 impl TryFrom<(String, String)> for Identifier {
     type Error = error_stack::Report<errors::ParsingError>;
     fn try_from(value: (String, String)) -> Result<Self, Self::Error> {
-        let did = value.0.as_str();
-        let kid = value.1;
-
-        match (did, kid) {
-            ("User", kid) => Ok(Self::User(kid)),
-            ("Merchant", kid) => Ok(Self::Merchant(kid)),
-            ("UserAuth", kid) => Ok(Self::UserAuth(kid)),
-            ("Entity", kid) => Ok(Self::Entity(kid)),
-            ("ReconMerchant", kid) => Ok(Self::ReconMerchant(kid)),
-            (_, _) => Err(error_stack::Report::from(
+        let (did, kid) = value;
+
+        if Self::is_valid_data_identifier(&did) {
+            Ok(Self::new(did, kid))
+        } else {
+            Err(error_stack::Report::from(
                 errors::ParsingError::ParsingFailed(String::from("Failed to parse Identifier")),
-            )),
+            ))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_identifier_is_alphanumeric() {
+        assert!(
+            DEFAULT_DATA_IDENTIFIERS
+                .iter()
+                .all(|data_identifier| Identifier::is_valid_data_identifier(data_identifier))
+        );
+        assert!(!Identifier::is_valid_data_identifier("User_Auth"));
+        assert!(!Identifier::is_valid_data_identifier(""));
+        assert!(!Identifier::is_valid_data_identifier(
+            &"A".repeat(MAX_DATA_IDENTIFIER_LEN + 1)
+        ));
+    }
+}
@@ -9,6 +9,7 @@ use diesel::{
     serialize::ToSql,
     sql_types,
 };
+use error_stack::IntoReport;
 use hyperswitch_masking::{Deserialize, Serialize, StrongSecret};
 use rustc_hash::{FxHashMap, FxHashSet};
 use scylla::{
@@ -16,10 +17,11 @@ use scylla::{
     frame::response::result::ColumnType,
 };
 use serde::de::{self, Deserializer, Unexpected, Visitor};
+use time::{OffsetDateTime, PrimitiveDateTime};
 
 use crate::{
     core::KeyDecrypter,
-    crypto::Source,
+    crypto::{Source, tr31::KeyBlockAttributes},
     env::observability as logger,
     errors::{self, SwitchError},
     multitenancy::TenantState,
@@ -33,6 +35,17 @@ pub struct Key {
     pub key: StrongSecret<[u8; 32]>,
     pub version: Version,
     pub source: Source,
+    pub parent: Option<ParentKey>,
+}
+
+/// Data key version of the parent identifier that wraps a child key in place of the KEK. Keys
+/// are resolved through their parents, so deleting a parent's key versions from the storage
+/// makes its children unusable without touching them. The service has no destroy operation of
+/// its own.
+#[derive(Clone, Debug)]
+pub struct ParentKey {
+    pub identifier: Identifier,
+    pub version: Version,
 }
 
 impl Key {
@@ -61,31 +74,53 @@ impl Key {
         identifier: &Identifier,
         version: FxHashSet<Version>,
     ) -> errors::CustomResult<FxHashMap<Version, Self>, errors::DatabaseError> {
-        let db = state.get_db_pool();
-        let get_and_decrypt_key = |v: Version| async move {
-            let key = db.get_key(v, identifier).await?;
-            key.decrypt(state).await.switch()
-        };
+        let mut keys = FxHashMap::default();
+        let mut missing = Vec::new();
+        for v in version {
+            let cache_key = cache::Key::from_state(state, format!("key_{identifier}:{v}"));
+            match cache::KEY_CACHE.get(&cache_key).await {
+                Some(key) => {
+                    keys.insert(v, key);
+                }
+                None => missing.push(v),
+            }
+        }
+        if missing.is_empty() {
+            return Ok(keys);
+        }
 
-        let futures = version.into_iter().map(|v| async move {
-            Ok::<_, error_stack::Report<errors::DatabaseError>>((
-                v,
-                cache::get_or_populate_cache(
-                    state,
-                    format!("key_{identifier}:{v}"),
-                    &cache::KEY_CACHE,
-                    get_and_decrypt_key(v),
+        // The versions missing from the cache are read and unwrapped together
+        let data_keys = state.get_db_pool().get_keys(identifier, &missing).await?;
+        error_stack::ensure!(
+            data_keys.len() == missing.len(),
+            errors::DatabaseError::NotFound
+        );
+
+        for key in data_keys.decrypt(state).await.switch()? {
+            cache::KEY_CACHE
+                .push(
+                    cache::Key::from_state(state, format!("key_{identifier}:{}", key.version)),
+                    key.clone(),
                 )
-                .await?,
-            ))
-        });
+                .await;
+            keys.insert(key.version, key);
+        }
 
-        Ok(FxHashMap::from_iter(
-            futures::future::try_join_all(futures).await?,
-        ))
+        Ok(keys)
     }
 }
 
+/// Attributes stored alongside a data key version, persisted as JSON in `key_metadata`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct KeyMetadata {
+    /// Header attributes of the TR-31 key block the key was imported from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_block: Option<KeyBlockAttributes>,
+    /// Hex encoded identifier of the import token the key was imported with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_token_id: Option<String>,
+}
+
 #[derive(AsExpression, Eq, PartialEq, Debug, Clone, Copy, Hash)]
 #[diesel(sql_type = diesel::sql_types::Integer)]
 pub struct Version(i32);
@@ -170,7 +205,7 @@ impl Version {
         let latest_version = db.get_latest_version(identifier);
         let v = cache::get_or_populate_cache(
             state,
-            format!("latest_version_{identifier}"),
+            Self::cache_key(identifier),
             &cache::VERSION_CACHE,
             latest_version,
         )
@@ -179,8 +214,49 @@ impl Version {
         v.unwrap_or_default()
     }
 
+    /// Drops the cached latest version of this instance, see [`Self::watch_rollbacks`] for the
+    /// other instances
+    pub async fn invalidate_latest(identifier: &Identifier, state: &TenantState) {
+        cache::VERSION_CACHE
+            .remove(&cache::Key::from_state(state, Self::cache_key(identifier)))
+            .await;
+    }
+
+    /// Drops the cached latest version of the identifiers rolled back by any instance, polling
+    /// the storage of the tenant every [`cache::ROLLBACK_POLL_INTERVAL`]
+    pub async fn watch_rollbacks(state: TenantState) {
+        let db = state.get_db_pool();
+        let mut interval = tokio::time::interval(cache::ROLLBACK_POLL_INTERVAL);
+        let mut polled_at = OffsetDateTime::now_utc();
+        loop {
+            interval.tick().await;
+            let now = OffsetDateTime::now_utc();
+            let since = polled_at - cache::ROLLBACK_POLL_OVERLAP;
+
+            match db
+                .get_rolled_back(PrimitiveDateTime::new(since.date(), since.time()))
+                .await
+            {
+                Ok(identifiers) => {
+                    for identifier in &identifiers {
+                        Self::invalidate_latest(identifier, &state).await;
+                    }
+                    polled_at = now;
+                }
+                Err(err) => logger::error!(?err, "Failed to poll the rolled back keys"),
+            }
+        }
+    }
+
+    fn cache_key(identifier: &Identifier) -> String {
+        format!("latest_version_{identifier}")
+    }
+
     pub fn increment(self) -> errors::CustomResult<Self, errors::ParsingError> {
-        Ok(Self(self.0 + 1))
+        self.0.checked_add(1).map(Self).ok_or(
+            errors::ParsingError::ParsingFailed(format!("No version after {self} is available"))
+                .into_report(),
+        )
     }
     pub fn inner(self) -> i32 {
         self.0
@@ -271,4 +347,11 @@ mod tests {
 
         assert_eq!(actual, expected)
     }
+
+    #[allow(clippy::unwrap_used)]
+    #[test]
+    fn test_version_increment() {
+        assert_eq!(Version(1).increment().unwrap(), Version(2));
+        assert!(Version(i32::MAX).increment().is_err());
+    }
 }
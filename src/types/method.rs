@@ -1,8 +1,10 @@
+use rayon::prelude::*;
+
 use crate::{
-    core::{DataDecrypter, DataEncrypter},
+    core::{ContentKey, DataDecrypter, DataEncrypter},
     errors,
     multitenancy::TenantState,
-    types::Identifier,
+    types::{Identifier, key::Version},
 };
 
 #[derive(Eq, PartialEq, Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -35,6 +37,20 @@ impl DecryptionType {
             }
         })
     }
+
+    /// Whether every ciphertext was encrypted for `purpose`
+    pub fn is_for_purpose(&self, purpose: &str) -> bool {
+        let matches = |data: &super::EncryptedData| data.purpose.as_deref() == Some(purpose);
+        match self {
+            Self::Single(data) => matches(data),
+            Self::Batch(data) => data.0.values().all(matches),
+            Self::MultiBatch(data) => data
+                .0
+                .iter()
+                .flat_map(|group| group.0.values())
+                .all(matches),
+        }
+    }
 }
 
 impl EncryptionType {
@@ -42,13 +58,41 @@ impl EncryptionType {
         self,
         state: &TenantState,
         identifier: &Identifier,
+        purpose: Option<&str>,
+        version: Option<Version>,
     ) -> errors::CustomResult<DecryptionType, errors::CryptoError> {
         Ok(match self {
-            Self::Single(data) => DecryptionType::Single(data.encrypt(state, identifier).await?),
-            Self::Batch(data) => DecryptionType::Batch(data.encrypt(state, identifier).await?),
+            Self::Single(data) => {
+                DecryptionType::Single(data.encrypt(state, identifier, purpose, version).await?)
+            }
+            Self::Batch(data) => {
+                DecryptionType::Batch(data.encrypt(state, identifier, purpose, version).await?)
+            }
             Self::MultiBatch(data) => {
-                DecryptionType::MultiBatch(data.encrypt(state, identifier).await?)
+                DecryptionType::MultiBatch(data.encrypt(state, identifier, purpose, version).await?)
             }
         })
     }
+
+    /// Encrypts the data with a content key generated for the request, which every ciphertext
+    /// carries in its wrapped form
+    pub fn encrypt_with_content_key(
+        self,
+        state: &TenantState,
+        key: &ContentKey,
+    ) -> errors::CustomResult<DecryptionType, errors::CryptoError> {
+        state.thread_pool.install(|| {
+            Ok(match self {
+                Self::Single(data) => DecryptionType::Single(key.encrypt(data)?),
+                Self::Batch(data) => DecryptionType::Batch(key.encrypt_group(data)?),
+                Self::MultiBatch(data) => DecryptionType::MultiBatch(
+                    data.0
+                        .into_par_iter()
+                        .map(|group| key.encrypt_group(group))
+                        .collect::<errors::CustomResult<Vec<_>, errors::CryptoError>>()
+                        .map(super::MultipleEncryptionDataGroup)?,
+                ),
+            })
+        })
+    }
 }
@@ -1,7 +1,15 @@
 pub mod data_key;
 mod decryption;
 mod encryption;
+mod key_block;
+mod key_wrap;
+mod lease;
+mod reencryption;
 
 pub use data_key::*;
 pub(crate) use decryption::*;
 pub(crate) use encryption::*;
+pub use key_block::*;
+pub use key_wrap::*;
+pub(crate) use lease::*;
+pub(crate) use reencryption::*;
@@ -1,11 +1,16 @@
 use serde::{Deserialize, Serialize};
 
-use crate::types::Identifier;
+use crate::{
+    crypto::{Source, wrapping_key::WrappingAlgorithm},
+    types::{Identifier, key::Version},
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct CreateDataKeyRequest {
     #[serde(flatten)]
     pub identifier: Identifier,
+    /// Wraps the data key with the latest key of this identifier instead of the KEK
+    pub parent: Option<Identifier>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -14,9 +19,38 @@ pub struct RotateDataKeyRequest {
     pub identifier: Identifier,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct RollbackDataKeyRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct TransferKeyRequest {
     #[serde(flatten)]
     pub identifier: Identifier,
     pub key: hyperswitch_masking::StrongSecret<String>,
 }
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateWrappingKeyRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    /// Defaults to the next version of the identifier
+    pub key_version: Option<Version>,
+    #[serde(default)]
+    pub algorithm: WrappingAlgorithm,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportDataKeyRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub import_token: String,
+    /// Base64 encoded key wrapped under the public key issued along with the import token
+    pub wrapped_key: String,
+    pub kcv: String,
+    /// Source the key is recorded with, defaults to the key manager of this deployment which
+    /// is also the only source it can decrypt the stored key with
+    pub source: Option<Source>,
+}
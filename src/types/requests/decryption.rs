@@ -7,4 +7,6 @@ pub struct DecryptionRequest {
     #[serde(flatten)]
     pub identifier: Identifier,
     pub data: DecryptionType,
+    /// Rejects the request unless all the data was encrypted for this purpose
+    pub purpose: Option<String>,
 }
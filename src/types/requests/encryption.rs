@@ -1,12 +1,35 @@
 use serde::{Deserialize, Serialize};
 
-use crate::types::{core::Identifier, method::EncryptionType};
+use crate::types::{
+    core::{Identifier, key::Version},
+    method::EncryptionType,
+};
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct EncryptDataRequest {
     #[serde(flatten)]
     pub identifier: Identifier,
     pub data: EncryptionType,
+    #[serde(default)]
+    pub mode: EncryptionMode,
+    /// Encrypts with the sub-key of the data key derived for this purpose
+    pub purpose: Option<String>,
+    /// Other identifiers that can decrypt the data with their own data keys
+    #[serde(default)]
+    pub shared_with: Vec<Identifier>,
+    /// Encrypts with this version of the data key instead of the latest one
+    pub key_version: Option<Version>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionMode {
+    /// Encrypted with the latest data key version of the identifier
+    #[default]
+    Managed,
+    /// Encrypted with a fresh data key that is wrapped by the KMS and embedded in the ciphertext,
+    /// so that it can be decrypted without the data key store
+    SelfContained,
 }
 
 #[cfg(test)]
@@ -39,8 +62,12 @@ mod tests {
         hash.insert(String::from("ff"), DecryptedData::from_data(data.into()));
 
         let expected_data = EncryptDataRequest {
-            identifier: Identifier::User(String::from("123")),
+            identifier: Identifier::new(String::from("User"), String::from("123")),
             data: EncryptionType::Batch(DecryptedDataGroup(hash)),
+            mode: EncryptionMode::Managed,
+            purpose: None,
+            shared_with: Vec::new(),
+            key_version: None,
         };
 
         assert_eq!(actual_data, expected_data);
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::tr31::KeyBlockAttributes,
+    types::{Identifier, key::Version},
+};
+
+/// A cripta data key used as the TR-31 key block protection key.
+#[derive(Deserialize, Serialize)]
+pub struct KeyBlockProtectionKey {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportKeyBlockRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub kbpk: KeyBlockProtectionKey,
+    pub key_block: hyperswitch_masking::StrongSecret<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ExportKeyBlockRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    /// Defaults to the latest version
+    pub key_version: Option<Version>,
+    pub kbpk: KeyBlockProtectionKey,
+    /// Only used for keys which were not imported from a key block and only when their export is
+    /// enabled, imported keys are always exported with their stored attributes. Defaults to an
+    /// exportable AES data encryption key, which may only be restricted.
+    pub attributes: Option<KeyBlockAttributes>,
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::key_wrap::KeyWrapAlgorithm,
+    types::{Identifier, key::Version},
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct WrapKeyRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    #[serde(default)]
+    pub algorithm: KeyWrapAlgorithm,
+    #[serde(flatten)]
+    pub key: KeyToWrap,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum KeyToWrap {
+    /// Base64 encoded plaintext key
+    Plaintext {
+        key: hyperswitch_masking::StrongSecret<String>,
+    },
+    /// Key wrapped by another KEK managed in cripta (e.g. a partner's KEK). The key is unwrapped
+    /// and re-wrapped without leaving the service.
+    Import { unwrap_with: UnwrapKeyRequest },
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UnwrapKeyRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+    #[serde(default)]
+    pub algorithm: KeyWrapAlgorithm,
+    pub wrapped_key: String,
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+use crate::types::Identifier;
+
+#[derive(Deserialize)]
+pub struct LeaseDataKeyRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    /// Lifetime of the lease in seconds, capped by the configured maximum
+    pub ttl: Option<u64>,
+    /// Number of encryptions allowed with the leased key, capped by the configured maximum
+    pub max_uses: Option<u64>,
+    /// Name of the configured recipient to seal the data key to. The key is returned as plaintext
+    /// base64 when this is not provided, which is only allowed to clients that authenticated with
+    /// a certificate over mTLS.
+    pub wrap_with: Option<String>,
+}
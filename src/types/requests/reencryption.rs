@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::core::{EncryptedData, Identifier};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReEncryptDataRequest {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub data: EncryptedData,
+    pub recipient: String,
+}
@@ -1,7 +1,15 @@
 mod datakey;
 mod decryption;
 mod encryption;
+mod key_block;
+mod key_wrap;
+mod lease;
+mod reencryption;
 
 pub use datakey::*;
 pub use decryption::*;
 pub use encryption::*;
+pub use key_block::*;
+pub use key_wrap::*;
+pub use lease::*;
+pub use reencryption::*;
@@ -1,10 +1,54 @@
 use serde::{Deserialize, Serialize};
 
-use crate::types::{Identifier, key::Version};
+use crate::{
+    crypto::wrapping_key::WrappingAlgorithm,
+    types::{Identifier, key::Version},
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct DataKeyCreateResponse {
     #[serde(flatten)]
     pub identifier: Identifier,
     pub key_version: Version,
+    /// Key check value, the first three bytes of the AES-CMAC of a zero block
+    pub kcv: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DataKeyRotateResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+    pub kcv: String,
+    /// `false` when a concurrent rotation created the returned version
+    pub created: bool,
+    /// Descendants rotated along with the key
+    pub rotated_children: Vec<Identifier>,
+    /// Descendants that could not be rotated, with their own descendants. Rotating each of them
+    /// again completes the cascade.
+    pub failed_children: Vec<Identifier>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DataKeyRollbackResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    /// Version data is encrypted with again
+    pub key_version: Version,
+    pub kcv: String,
+    pub rolled_back_version: Version,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WrappingKeyResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    /// Version the key is imported as, the import token is only accepted for it
+    pub key_version: Version,
+    pub algorithm: WrappingAlgorithm,
+    /// Base64 encoded public key to wrap the key under
+    pub public_key: String,
+    pub import_token: String,
+    /// Unix timestamp after which the import token is rejected
+    pub expires_at: i64,
 }
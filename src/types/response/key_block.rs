@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::tr31::KeyBlockAttributes,
+    types::{Identifier, key::Version},
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportKeyBlockResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+    pub kcv: String,
+    pub attributes: KeyBlockAttributes,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ExportKeyBlockResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+    pub key_block: String,
+}
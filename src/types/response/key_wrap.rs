@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::key_wrap::KeyWrapAlgorithm,
+    types::{Identifier, key::Version},
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct WrapKeyResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+    pub algorithm: KeyWrapAlgorithm,
+    pub wrapped_key: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UnwrapKeyResponse {
+    pub key: hyperswitch_masking::StrongSecret<String>,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Identifier, key::Version};
+
+#[derive(Deserialize, Serialize)]
+pub struct LeaseDataKeyResponse {
+    #[serde(flatten)]
+    pub identifier: Identifier,
+    pub key_version: Version,
+    /// Base64 encoded data key, or the data key sealed to the requested public key
+    pub key: hyperswitch_masking::StrongSecret<String>,
+    /// Unix timestamp after which the key must no longer be used for encryption
+    pub expires_at: i64,
+    pub max_uses: u64,
+}
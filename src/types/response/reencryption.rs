@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::recipient::RecipientAlgorithm;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReEncryptionResponse {
+    pub recipient: String,
+    pub algorithm: RecipientAlgorithm,
+    pub data: String,
+}